@@ -0,0 +1,1482 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use chrono::{DateTime, Datelike, Timelike};
+use regex::Regex;
+
+use crate::models::{
+    ActiveContributors, AuthorEdge, AuthorMonthMatrix, ChurnVelocity, Commit, CommitCadence, CommitSizeStats,
+    DirectoryReport, DirectorySort, DirectoryTruckFactor, ExtensionChurnBucket, FileChurn, FileLineCount, FileOwnership, GrowthRatio,
+    MessageQuality, MessageRules, OwnedFilesCommit, PrimaryLanguage, RelativeChurn, ResurrectedFile, ReviewCoverage, ReviewStats, RevertPair, StaleFile, TestCategory, TestDiscipline,
+};
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+const SECONDS_PER_HOUR: f64 = 3600.0;
+const CONVENTIONAL_SUBJECT: &str =
+    r"^(feat|fix|docs|style|refactor|perf|test|build|ci|chore|revert)(\([^)]+\))?!?: \S";
+const LANGUAGE_EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", "Rust"), ("py", "Python"), ("pyx", "Python"), ("js", "JavaScript"), ("jsx", "JavaScript"),
+    ("ts", "TypeScript"), ("tsx", "TypeScript"), ("go", "Go"), ("java", "Java"), ("kt", "Kotlin"),
+    ("scala", "Scala"), ("c", "C"), ("h", "C"), ("cc", "C++"), ("cpp", "C++"), ("hpp", "C++"),
+    ("cs", "C#"), ("rb", "Ruby"), ("php", "PHP"), ("swift", "Swift"), ("m", "Objective-C"),
+    ("sh", "Shell"), ("sql", "SQL"), ("lua", "Lua"), ("ex", "Elixir"), ("exs", "Elixir"),
+    ("hs", "Haskell"), ("clj", "Clojure"), ("dart", "Dart"),
+];
+
+pub fn file_churn(commits: &[Commit], half_life_days: Option<f64>, now: i64) -> Vec<FileChurn> {
+    let mut result = churn_by_file(commits, half_life_days, now);
+    result.sort_by(by_churn_desc);
+    result
+}
+
+/// Same order as `file_churn`, but only the first `n` entries get sorted.
+pub fn top_churned_files(commits: &[Commit], n: usize) -> Vec<FileChurn> {
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut result = churn_by_file(commits, None, 0);
+    if n < result.len() {
+        result.select_nth_unstable_by(n - 1, by_churn_desc);
+        result.truncate(n);
+    }
+    result.sort_by(by_churn_desc);
+    result
+}
+
+fn by_churn_desc(a: &FileChurn, b: &FileChurn) -> std::cmp::Ordering {
+    b.churn.cmp(&a.churn).then_with(|| a.filename.cmp(&b.filename))
+}
+
+fn churn_by_file(commits: &[Commit], half_life_days: Option<f64>, now: i64) -> Vec<FileChurn> {
+    let mut churn_by_file: HashMap<&str, FileChurn> = HashMap::new();
+
+    for commit in commits {
+        let weight = recency_weight(commit.timestamp, half_life_days, now);
+
+        for file in &commit.files {
+            let entry = churn_by_file.entry(file.filename.as_str())
+                .or_insert_with(|| FileChurn {
+                    filename: file.filename.clone(),
+                    additions: 0,
+                    deletions: 0,
+                    churn: 0,
+                    net_lines: 0,
+                    weighted_churn: 0.0,
+                });
+
+            let churn = file.additions + file.deletions;
+            entry.additions += file.additions;
+            entry.deletions += file.deletions;
+            entry.churn += churn;
+            entry.net_lines += file.additions as i64 - file.deletions as i64;
+            entry.weighted_churn += churn as f64 * weight;
+        }
+    }
+
+    churn_by_file.into_values().collect()
+}
+
+pub fn relative_churn(churn: &[FileChurn], line_counts: &[FileLineCount], unstable_threshold: f64) -> Vec<RelativeChurn> {
+    let lines_by_file: HashMap<&str, usize> = line_counts.iter()
+        .filter(|count| !count.binary && count.lines > 0)
+        .map(|count| (count.filename.as_str(), count.lines))
+        .collect();
+
+    // Files gone from HEAD (or empty/binary there) have no size to normalize against.
+    let mut result: Vec<RelativeChurn> = churn.iter()
+        .filter_map(|file| {
+            let lines = *lines_by_file.get(file.filename.as_str())?;
+            let relative_churn = file.churn as f64 / lines as f64;
+            Some(RelativeChurn {
+                filename: file.filename.clone(),
+                churn: file.churn,
+                lines,
+                relative_churn,
+                unstable: relative_churn >= unstable_threshold,
+            })
+        })
+        .collect();
+
+    result.sort_by(|a, b| {
+        b.relative_churn.total_cmp(&a.relative_churn).then_with(|| a.filename.cmp(&b.filename))
+    });
+    result
+}
+
+pub fn primary_language(line_counts: &[FileLineCount]) -> Option<PrimaryLanguage> {
+    let mut lines_by_language: HashMap<&str, usize> = HashMap::new();
+    for count in line_counts.iter().filter(|count| !count.binary) {
+        if let Some(language) = file_language(&count.filename) {
+            *lines_by_language.entry(language).or_insert(0) += count.lines;
+        }
+    }
+
+    let total_lines: usize = lines_by_language.values().sum();
+    let (language, lines) = lines_by_language.into_iter()
+        .filter(|(_, lines)| *lines > 0)
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))?;
+
+    Some(PrimaryLanguage {
+        language: language.to_string(),
+        lines,
+        total_lines,
+        percentage: lines as f64 / total_lines as f64 * 100.0,
+    })
+}
+
+/// Tracked files with no change in `commits` during the `days` before `now`, oldest first.
+pub fn stale_files(tracked_files: &[String], commits: &[Commit], days: u32, now: i64) -> Vec<StaleFile> {
+    let cutoff = now - days as i64 * SECONDS_PER_DAY as i64;
+    let mut last_modified: HashMap<&str, i64> = HashMap::new();
+    for commit in commits {
+        for file in &commit.files {
+            let latest = last_modified.entry(file.filename.as_str()).or_insert(commit.timestamp);
+            *latest = (*latest).max(commit.timestamp);
+        }
+    }
+
+    let mut result: Vec<StaleFile> = tracked_files.iter()
+        .filter_map(|filename| {
+            let last_modified = last_modified.get(filename.as_str()).copied();
+            last_modified.is_none_or(|timestamp| timestamp < cutoff).then(|| StaleFile {
+                filename: filename.clone(),
+                last_modified,
+            })
+        })
+        .collect();
+
+    result.sort_by(|a, b| a.last_modified.cmp(&b.last_modified).then_with(|| a.filename.cmp(&b.filename)));
+    result
+}
+
+fn file_language(filename: &str) -> Option<&'static str> {
+    let extension = Path::new(filename).extension()?.to_str()?.to_ascii_lowercase();
+    LANGUAGE_EXTENSIONS.iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, language)| *language)
+}
+
+fn recency_weight(timestamp: i64, half_life_days: Option<f64>, now: i64) -> f64 {
+    match half_life_days {
+        Some(half_life) if half_life > 0.0 => {
+            let age_days = (now - timestamp).max(0) as f64 / SECONDS_PER_DAY;
+            0.5_f64.powf(age_days / half_life)
+        },
+        _ => 1.0,
+    }
+}
+
+pub fn file_ownership(commits: &[Commit]) -> Vec<FileOwnership> {
+    let mut author_changes: HashMap<&str, HashMap<&str, u32>> = HashMap::new();
+
+    for commit in commits {
+        for file in &commit.files {
+            *author_changes.entry(file.filename.as_str())
+                .or_default()
+                .entry(commit.author_email.as_str())
+                .or_insert(0) += 1;
+        }
+    }
+
+    let mut result: Vec<FileOwnership> = author_changes.into_iter()
+        .filter_map(|(filename, authors)| {
+            let total_changes: u32 = authors.values().sum();
+            let (dominant_author, dominant_changes) = authors.iter()
+                .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))?;
+
+            Some(FileOwnership {
+                filename: filename.to_string(),
+                dominant_author: dominant_author.to_string(),
+                ownership_ratio: *dominant_changes as f64 / total_changes as f64,
+                contributor_count: authors.len(),
+                total_changes,
+                author_changes: authors.iter()
+                    .map(|(author, changes)| (author.to_string(), *changes))
+                    .collect(),
+            })
+        })
+        .collect();
+
+    result.sort_by(|a, b| b.total_changes.cmp(&a.total_changes).then_with(|| a.filename.cmp(&b.filename)));
+    result
+}
+
+/// Rolls file ownership up to directories `depth` components deep (files above that depth count
+/// towards their own directory, root files towards "."). The truck factor is the fewest authors
+/// whose changes add up to more than half of the directory's total.
+pub fn directory_truck_factor(commits: &[Commit], depth: usize) -> Vec<DirectoryTruckFactor> {
+    let mut author_changes: HashMap<String, HashMap<String, u32>> = HashMap::new();
+    for ownership in file_ownership(commits) {
+        let directory_changes = author_changes.entry(directory_at_depth(&ownership.filename, depth)).or_default();
+        for (author, changes) in ownership.author_changes {
+            *directory_changes.entry(author).or_insert(0) += changes;
+        }
+    }
+
+    let mut result: Vec<DirectoryTruckFactor> = author_changes.into_iter()
+        .map(|(directory, authors)| {
+            let total_changes: u32 = authors.values().sum();
+            let mut ranked: Vec<(String, u32)> = authors.into_iter().collect();
+            ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+            let mut covered = 0;
+            let key_authors: Vec<String> = ranked.into_iter()
+                .take_while(|(_, changes)| {
+                    let needed = covered * 2 <= total_changes;
+                    covered += changes;
+                    needed
+                })
+                .map(|(author, _)| author)
+                .collect();
+
+            DirectoryTruckFactor {
+                directory,
+                truck_factor: key_authors.len(),
+                high_risk: key_authors.len() == 1,
+                key_authors,
+                total_changes,
+            }
+        })
+        .collect();
+
+    result.sort_by(|a, b| a.truck_factor.cmp(&b.truck_factor)
+        .then_with(|| b.total_changes.cmp(&a.total_changes))
+        .then_with(|| a.directory.cmp(&b.directory)));
+    result
+}
+
+/// One row per directory at `depth` (grouped like `directory_truck_factor`) combining its
+/// dominant owner, contributor count, last commit timestamp and total churn.
+pub fn directory_report(commits: &[Commit], depth: usize, sort: DirectorySort) -> Vec<DirectoryReport> {
+    let mut author_changes: HashMap<String, HashMap<String, u32>> = HashMap::new();
+    for ownership in file_ownership(commits) {
+        let directory_changes = author_changes.entry(directory_at_depth(&ownership.filename, depth)).or_default();
+        for (author, changes) in ownership.author_changes {
+            *directory_changes.entry(author).or_insert(0) += changes;
+        }
+    }
+
+    let mut churn: HashMap<String, u32> = HashMap::new();
+    for file in churn_by_file(commits, None, 0) {
+        *churn.entry(directory_at_depth(&file.filename, depth)).or_insert(0) += file.churn;
+    }
+
+    let mut last_modified: HashMap<String, i64> = HashMap::new();
+    for commit in commits {
+        for file in &commit.files {
+            let latest = last_modified.entry(directory_at_depth(&file.filename, depth)).or_insert(commit.timestamp);
+            *latest = (*latest).max(commit.timestamp);
+        }
+    }
+
+    let mut result: Vec<DirectoryReport> = author_changes.into_iter()
+        .filter_map(|(directory, authors)| {
+            let total_changes: u32 = authors.values().sum();
+            let (dominant_author, dominant_changes) = authors.iter()
+                .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))?;
+
+            Some(DirectoryReport {
+                dominant_author: dominant_author.clone(),
+                ownership_ratio: *dominant_changes as f64 / total_changes as f64,
+                contributor_count: authors.len(),
+                last_modified: last_modified.get(&directory).copied().unwrap_or_default(),
+                churn: churn.get(&directory).copied().unwrap_or_default(),
+                directory,
+            })
+        })
+        .collect();
+
+    match sort {
+        DirectorySort::Churn => result.sort_by(|a, b| b.churn.cmp(&a.churn).then_with(|| a.directory.cmp(&b.directory))),
+        DirectorySort::Staleness => result.sort_by(|a, b| {
+            a.last_modified.cmp(&b.last_modified).then_with(|| a.directory.cmp(&b.directory))
+        }),
+    }
+    result
+}
+
+fn directory_at_depth(filename: &str, depth: usize) -> String {
+    match filename.rsplit_once('/') {
+        Some((parent, _)) => parent.split('/').take(depth).collect::<Vec<_>>().join("/"),
+        None => ".".to_string(),
+    }
+}
+
+pub fn commits_touching_owned_files(commits: &[Commit], owner_email: &str) -> Vec<OwnedFilesCommit> {
+    let owned: HashSet<String> = file_ownership(commits).into_iter()
+        .filter(|ownership| ownership.dominant_author == owner_email)
+        .map(|ownership| ownership.filename)
+        .collect();
+
+    commits.iter()
+        .filter(|commit| commit.author_email != owner_email)
+        .filter_map(|commit| {
+            let owned_files: Vec<String> = commit.files.iter()
+                .filter(|file| owned.contains(&file.filename))
+                .map(|file| file.filename.clone())
+                .collect();
+
+            if owned_files.is_empty() {
+                None
+            } else {
+                Some(OwnedFilesCommit { commit: commit.clone(), owned_files })
+            }
+        })
+        .collect()
+}
+
+pub fn collaboration_graph(commits: &[Commit]) -> Vec<AuthorEdge> {
+    let mut authors_by_file: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for commit in commits {
+        for file in &commit.files {
+            authors_by_file.entry(file.filename.as_str())
+                .or_default()
+                .insert(commit.author_email.as_str());
+        }
+    }
+
+    let mut shared_files: HashMap<(&str, &str), u32> = HashMap::new();
+    for authors in authors_by_file.values() {
+        let authors: Vec<&str> = authors.iter().copied().collect();
+        for (i, author_a) in authors.iter().enumerate() {
+            for author_b in &authors[i + 1..] {
+                *shared_files.entry((author_a, author_b)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut edges: Vec<AuthorEdge> = shared_files.into_iter()
+        .map(|((author_a, author_b), weight)| AuthorEdge {
+            author_a: author_a.to_string(),
+            author_b: author_b.to_string(),
+            weight,
+        })
+        .collect();
+
+    edges.sort_by(|a, b| {
+        b.weight.cmp(&a.weight)
+            .then_with(|| a.author_a.cmp(&b.author_a))
+            .then_with(|| a.author_b.cmp(&b.author_b))
+    });
+    edges
+}
+
+pub fn co_change_clusters(commits: &[Commit], min_coupling: f64, min_cluster_size: usize) -> Vec<Vec<String>> {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut names: Vec<&str> = Vec::new();
+    let mut change_counts: Vec<u32> = Vec::new();
+    let mut shared_changes: HashMap<(usize, usize), u32> = HashMap::new();
+    for commit in commits {
+        let mut files: Vec<usize> = commit.files.iter()
+            .map(|file| {
+                *index.entry(file.filename.as_str()).or_insert_with(|| {
+                    names.push(file.filename.as_str());
+                    change_counts.push(0);
+                    names.len() - 1
+                })
+            })
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        for (i, &file_a) in files.iter().enumerate() {
+            change_counts[file_a] += 1;
+            for &file_b in &files[i + 1..] {
+                *shared_changes.entry((file_a, file_b)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut parents: Vec<usize> = (0..names.len()).collect();
+    for ((file_a, file_b), shared) in shared_changes {
+        let coupling = shared as f64 / change_counts[file_a].max(change_counts[file_b]) as f64;
+        if coupling >= min_coupling {
+            let root_a = find_root(&mut parents, file_a);
+            let root_b = find_root(&mut parents, file_b);
+            parents[root_a.max(root_b)] = root_a.min(root_b);
+        }
+    }
+
+    let mut members: HashMap<usize, Vec<String>> = HashMap::new();
+    for (file, name) in names.iter().enumerate() {
+        let root = find_root(&mut parents, file);
+        members.entry(root).or_default().push(name.to_string());
+    }
+
+    let mut clusters: Vec<Vec<String>> = members.into_values()
+        .filter(|cluster| cluster.len() > 1 && cluster.len() >= min_cluster_size)
+        .map(|mut cluster| {
+            cluster.sort();
+            cluster
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    clusters
+}
+
+fn find_root(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+    node
+}
+
+pub fn commit_size_stats(commits: &[Commit], exclude_merges: bool, exclude_binary_only: bool) -> CommitSizeStats {
+    let mut sizes: Vec<u32> = commits.iter()
+        .filter(|commit| !(exclude_merges && commit.parents.len() > 1))
+        .filter(|commit| {
+            !(exclude_binary_only && !commit.files.is_empty() && commit.files.iter().all(|file| file.binary))
+        })
+        .map(|commit| commit.files.iter().map(|file| file.additions + file.deletions).sum())
+        .collect();
+
+    if sizes.is_empty() {
+        return CommitSizeStats::default();
+    }
+    sizes.sort_unstable();
+
+    // Nearest-rank percentile over the sorted sizes.
+    let percentile = |p: f64| sizes[((p / 100.0 * sizes.len() as f64).ceil() as usize).clamp(1, sizes.len()) - 1];
+
+    CommitSizeStats {
+        commit_count: sizes.len(),
+        mean: sizes.iter().map(|&size| size as f64).sum::<f64>() / sizes.len() as f64,
+        max: sizes[sizes.len() - 1],
+        p50: percentile(50.0),
+        p90: percentile(90.0),
+        p99: percentile(99.0),
+    }
+}
+
+pub fn commits_by_file(commits: &[Commit]) -> HashMap<String, Vec<String>> {
+    let chronological = chronological(commits);
+
+    // Histories follow renames (and are duplicated on copies) so they end up under the latest name.
+    let mut history: HashMap<String, Vec<(usize, &str)>> = HashMap::new();
+    for (position, commit) in chronological.iter().enumerate() {
+        for file in &commit.files {
+            let mut entries = history.remove(&file.filename).unwrap_or_default();
+            if let Some(old_filename) = &file.old_filename {
+                if file.status.starts_with('R') {
+                    entries.extend(history.remove(old_filename).unwrap_or_default());
+                } else if let Some(source) = history.get(old_filename) {
+                    entries.extend(source.iter().copied());
+                }
+            }
+            entries.push((position, commit.hash.as_str()));
+            history.insert(file.filename.clone(), entries);
+        }
+    }
+
+    history.into_iter()
+        .map(|(filename, mut entries)| {
+            entries.sort_unstable();
+            entries.dedup();
+            (filename, entries.into_iter().map(|(_, hash)| hash.to_string()).collect())
+        })
+        .collect()
+}
+
+pub fn commit_cadence(commits: &[Commit]) -> HashMap<String, CommitCadence> {
+    let mut timestamps_by_author: HashMap<&str, Vec<i64>> = HashMap::new();
+    for commit in commits {
+        timestamps_by_author.entry(commit.author_email.as_str())
+            .or_default()
+            .push(commit.timestamp);
+    }
+
+    timestamps_by_author.into_iter()
+        .map(|(author, mut timestamps)| {
+            timestamps.sort_unstable();
+            let mut gaps: Vec<i64> = timestamps.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            gaps.sort_unstable();
+
+            // Authors with a single commit have no gap to measure.
+            let median_gap_seconds = match gaps.len() {
+                0 => None,
+                len if len % 2 == 1 => Some(gaps[len / 2] as f64),
+                len => Some((gaps[len / 2 - 1] + gaps[len / 2]) as f64 / 2.0),
+            };
+
+            (author.to_string(), CommitCadence {
+                median_gap_hours: median_gap_seconds.map(|seconds| seconds / SECONDS_PER_HOUR),
+                commit_count: timestamps.len(),
+            })
+        })
+        .collect()
+}
+
+/// Rows follow `authors` (sorted emails) and columns every UTC month from the first commit to the
+/// last, so quiet months show up as zeros. Counts are commits, or added plus deleted lines.
+pub fn author_month_matrix(commits: &[Commit], count_lines: bool) -> AuthorMonthMatrix {
+    let month_of = |timestamp: i64| {
+        let date = DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+        date.year() * 12 + date.month0() as i32
+    };
+
+    let authors: Vec<String> = commits.iter()
+        .map(|commit| commit.author_email.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let (Some(first), Some(last)) = (
+        commits.iter().map(|commit| month_of(commit.timestamp)).min(),
+        commits.iter().map(|commit| month_of(commit.timestamp)).max(),
+    ) else {
+        return AuthorMonthMatrix { authors, months: Vec::new(), counts: Vec::new() };
+    };
+
+    let row_of: HashMap<&str, usize> = authors.iter()
+        .enumerate()
+        .map(|(row, author)| (author.as_str(), row))
+        .collect();
+    let mut counts = vec![vec![0; (last - first + 1) as usize]; authors.len()];
+    for commit in commits {
+        let amount = if count_lines { commit_churn(commit) } else { 1 };
+        counts[row_of[commit.author_email.as_str()]][(month_of(commit.timestamp) - first) as usize] += amount;
+    }
+
+    let months = (first..=last)
+        .map(|month| format!("{:04}-{:02}", month.div_euclid(12), month.rem_euclid(12) + 1))
+        .collect();
+    AuthorMonthMatrix { authors, months, counts }
+}
+
+/// Churn per `bucket_days` bucket (aligned to the Unix epoch, quiet buckets included) with
+/// `velocity` the mean churn over the last `smoothing_buckets` buckets up to and including it.
+pub fn churn_velocity(commits: &[Commit], bucket_days: u32, smoothing_buckets: usize) -> Vec<ChurnVelocity> {
+    if bucket_days == 0 || smoothing_buckets == 0 {
+        return Vec::new();
+    }
+    let bucket_seconds = bucket_days as i64 * SECONDS_PER_DAY as i64;
+    let Some((first, last)) = bucket_range(commits, bucket_seconds) else {
+        return Vec::new();
+    };
+
+    let mut churn = vec![0u32; (last - first + 1) as usize];
+    for commit in commits {
+        churn[(commit.timestamp.div_euclid(bucket_seconds) - first) as usize] += commit_churn(commit);
+    }
+
+    churn.iter()
+        .enumerate()
+        .map(|(i, &bucket_churn)| {
+            let window = &churn[(i + 1).saturating_sub(smoothing_buckets)..=i];
+            ChurnVelocity {
+                bucket_start: (first + i as i64) * bucket_seconds,
+                churn: bucket_churn,
+                velocity: window.iter().map(|&c| c as f64).sum::<f64>() / window.len() as f64,
+            }
+        })
+        .collect()
+}
+
+/// Additions and deletions per file extension in each `bucket_days` bucket. Every extension gets
+/// the same contiguous run of buckets; files without an extension are grouped under "".
+pub fn extension_churn_trend(commits: &[Commit], bucket_days: u32) -> HashMap<String, Vec<ExtensionChurnBucket>> {
+    let mut trend: HashMap<String, Vec<ExtensionChurnBucket>> = HashMap::new();
+    if bucket_days == 0 {
+        return trend;
+    }
+    let bucket_seconds = bucket_days as i64 * SECONDS_PER_DAY as i64;
+    let Some((first, last)) = bucket_range(commits, bucket_seconds) else {
+        return trend;
+    };
+
+    for commit in commits {
+        let bucket = (commit.timestamp.div_euclid(bucket_seconds) - first) as usize;
+        for file in &commit.files {
+            let extension = Path::new(&file.filename).extension()
+                .map_or_else(String::new, |ext| ext.to_string_lossy().to_string());
+            let series = trend.entry(extension).or_insert_with(|| {
+                (first..=last)
+                    .map(|bucket| ExtensionChurnBucket { bucket_start: bucket * bucket_seconds, additions: 0, deletions: 0 })
+                    .collect()
+            });
+            series[bucket].additions += file.additions;
+            series[bucket].deletions += file.deletions;
+        }
+    }
+
+    trend
+}
+
+pub fn growth_ratio_trend(commits: &[Commit], bucket_days: u32) -> Vec<GrowthRatio> {
+    if bucket_days == 0 {
+        return Vec::new();
+    }
+    let bucket_seconds = bucket_days as i64 * SECONDS_PER_DAY as i64;
+    let Some((first, last)) = bucket_range(commits, bucket_seconds) else {
+        return Vec::new();
+    };
+
+    let mut totals = vec![(0u32, 0u32); (last - first + 1) as usize];
+    for commit in commits {
+        let bucket = &mut totals[(commit.timestamp.div_euclid(bucket_seconds) - first) as usize];
+        for file in &commit.files {
+            bucket.0 += file.additions;
+            bucket.1 += file.deletions;
+        }
+    }
+
+    totals.into_iter()
+        .enumerate()
+        .map(|(i, (additions, deletions))| GrowthRatio {
+            bucket_start: (first + i as i64) * bucket_seconds,
+            additions,
+            deletions,
+            ratio: (deletions > 0).then(|| additions as f64 / deletions as f64),
+        })
+        .collect()
+}
+
+fn bucket_range(commits: &[Commit], bucket_seconds: i64) -> Option<(i64, i64)> {
+    let buckets = commits.iter().map(|commit| commit.timestamp.div_euclid(bucket_seconds));
+    Some((buckets.clone().min()?, buckets.max()?))
+}
+
+fn commit_churn(commit: &Commit) -> u32 {
+    commit.files.iter().map(|file| file.additions + file.deletions).sum()
+}
+
+/// Commit counts by weekday (Monday first) and hour. Times are shifted by `utc_offset_minutes`,
+/// or by each commit's own author offset when it is `None`.
+pub fn punchcard(commits: &[Commit], utc_offset_minutes: Option<i32>) -> Vec<Vec<u32>> {
+    let mut counts = vec![vec![0; 24]; 7];
+    for commit in commits {
+        let offset = utc_offset_minutes.unwrap_or(commit.utc_offset_minutes) as i64 * 60;
+        let local = DateTime::from_timestamp(commit.timestamp + offset, 0).unwrap_or_default();
+        counts[local.weekday().num_days_from_monday() as usize][local.hour() as usize] += 1;
+    }
+    counts
+}
+
+/// Distinct author emails in each `(period_end - window_days, period_end]` window, with
+/// `period_end` stepping from the first commit until it reaches the last one.
+pub fn active_contributors(commits: &[Commit], window_days: u32, step_days: u32) -> Vec<ActiveContributors> {
+    let mut timeline: Vec<(i64, &str)> = commits.iter()
+        .map(|commit| (commit.timestamp, commit.author_email.as_str()))
+        .collect();
+    timeline.sort_unstable();
+
+    let (Some(&(first, _)), Some(&(last, _))) = (timeline.first(), timeline.last()) else {
+        return Vec::new();
+    };
+    if window_days == 0 || step_days == 0 {
+        return Vec::new();
+    }
+    let window = window_days as i64 * SECONDS_PER_DAY as i64;
+    let step = step_days as i64 * SECONDS_PER_DAY as i64;
+
+    let mut in_window: HashMap<&str, usize> = HashMap::new();
+    let (mut entered, mut left) = (0, 0);
+    let mut series = Vec::new();
+    let mut period_end = first;
+    loop {
+        while entered < timeline.len() && timeline[entered].0 <= period_end {
+            *in_window.entry(timeline[entered].1).or_insert(0) += 1;
+            entered += 1;
+        }
+        while left < entered && timeline[left].0 <= period_end - window {
+            let author = timeline[left].1;
+            if let Some(count) = in_window.get_mut(author) {
+                *count -= 1;
+                if *count == 0 {
+                    in_window.remove(author);
+                }
+            }
+            left += 1;
+        }
+
+        series.push(ActiveContributors { period_end, active_authors: in_window.len() });
+        if period_end >= last {
+            break;
+        }
+        period_end += step;
+    }
+
+    series
+}
+
+pub fn resurrected_files(commits: &[Commit]) -> Vec<ResurrectedFile> {
+    let mut deleted_in: HashMap<&str, &str> = HashMap::new();
+    let mut resurrected = Vec::new();
+
+    for commit in chronological(commits) {
+        for file in &commit.files {
+            if file.status.starts_with('D') {
+                deleted_in.insert(file.filename.as_str(), commit.hash.as_str());
+            } else if let Some(deleted_hash) = deleted_in.remove(file.filename.as_str()) {
+                if file.status.starts_with('A') {
+                    resurrected.push(ResurrectedFile {
+                        filename: file.filename.clone(),
+                        deleted_in: deleted_hash.to_string(),
+                        readded_in: commit.hash.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    resurrected
+}
+
+fn chronological(commits: &[Commit]) -> Vec<&Commit> {
+    let mut chronological: Vec<&Commit> = commits.iter().collect();
+    chronological.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| b.index.cmp(&a.index)));
+    chronological
+}
+
+/// Scores each commit's subject and body against `rules`; the second value counts commits per
+/// score, indexed 0 to 4.
+pub fn message_quality(commits: &[Commit], rules: &MessageRules) -> (Vec<MessageQuality>, Vec<usize>) {
+    let conventional = Regex::new(CONVENTIONAL_SUBJECT).unwrap();
+    let mut distribution = vec![0; 5];
+
+    let scores = commits.iter()
+        .map(|commit| {
+            let subject_length = commit.message.trim().chars().count();
+            let checks = [
+                !commit.body.trim().is_empty(),
+                (rules.subject_min_length..=rules.subject_max_length).contains(&subject_length),
+                rules.issue_pattern.is_match(&commit.message) || rules.issue_pattern.is_match(&commit.body),
+                conventional.is_match(&commit.message),
+            ];
+            let score = checks.iter().filter(|&&passed| passed).count();
+            distribution[score] += 1;
+
+            MessageQuality {
+                hash: commit.hash.clone(),
+                has_body: checks[0],
+                subject_length_ok: checks[1],
+                references_issue: checks[2],
+                conventional: checks[3],
+                score,
+            }
+        })
+        .collect();
+
+    (scores, distribution)
+}
+
+/// Test files count as tests even when their extension is a code extension.
+pub fn test_discipline(
+    commits: &[Commit],
+    is_test_file: impl Fn(&str) -> bool,
+    is_code_file: impl Fn(&str) -> bool,
+) -> TestDiscipline {
+    let categories: Vec<(String, TestCategory)> = commits.iter()
+        .map(|commit| {
+            let (mut code, mut tests) = (false, false);
+            for file in &commit.files {
+                if is_test_file(&file.filename) {
+                    tests = true;
+                } else if is_code_file(&file.filename) {
+                    code = true;
+                }
+            }
+            let category = match (code, tests) {
+                (true, false) => TestCategory::CodeOnly,
+                (false, true) => TestCategory::TestsOnly,
+                (true, true) => TestCategory::CodeAndTests,
+                (false, false) => TestCategory::Neither,
+            };
+            (commit.hash.clone(), category)
+        })
+        .collect();
+
+    let count = |wanted: TestCategory| categories.iter().filter(|(_, category)| *category == wanted).count();
+    let (code_only, tests_only) = (count(TestCategory::CodeOnly), count(TestCategory::TestsOnly));
+    let (code_and_tests, neither) = (count(TestCategory::CodeAndTests), count(TestCategory::Neither));
+    let code_commits = code_only + code_and_tests;
+
+    TestDiscipline {
+        test_ratio: (code_commits > 0).then(|| code_and_tests as f64 / code_commits as f64),
+        categories,
+        code_only,
+        tests_only,
+        code_and_tests,
+        neither,
+    }
+}
+
+/// Review coverage per author from `Reviewed-by` trailers. Reviewers are identified by the email
+/// in angle brackets when present, otherwise by the trailer value, and count once per commit.
+pub fn review_stats(commits: &[Commit]) -> ReviewStats {
+    let mut per_author: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut per_reviewer: HashMap<String, usize> = HashMap::new();
+    for commit in commits {
+        let reviewers: BTreeSet<String> = commit.trailers.iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Reviewed-by"))
+            .flat_map(|(_, values)| values)
+            .map(|value| reviewer_identity(value))
+            .filter(|reviewer| !reviewer.is_empty())
+            .collect();
+
+        let counts = per_author.entry(commit.author_email.as_str()).or_insert((0, 0));
+        counts.0 += 1;
+        if !reviewers.is_empty() {
+            counts.1 += 1;
+        }
+        for reviewer in reviewers {
+            *per_reviewer.entry(reviewer).or_insert(0) += 1;
+        }
+    }
+
+    let mut coverage: Vec<ReviewCoverage> = per_author.into_iter()
+        .map(|(author, (commits, reviewed_commits))| ReviewCoverage {
+            author: author.to_string(),
+            commits,
+            reviewed_commits,
+            coverage: reviewed_commits as f64 / commits as f64,
+        })
+        .collect();
+    coverage.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.author.cmp(&b.author)));
+
+    let mut reviewers: Vec<(String, usize)> = per_reviewer.into_iter().collect();
+    reviewers.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    ReviewStats { coverage, reviewers }
+}
+
+fn reviewer_identity(value: &str) -> String {
+    let email = value.split_once('<').and_then(|(_, rest)| rest.split_once('>')).map(|(email, _)| email);
+    email.unwrap_or(value).trim().to_lowercase()
+}
+
+pub fn revert_pairs(commits: &[Commit]) -> Vec<RevertPair> {
+    commits.iter()
+        .filter_map(|revert| {
+            let reverted_hash = revert.reverted_hash.as_ref()?;
+            let original = commits.iter()
+                .find(|commit| commit.hash.starts_with(reverted_hash.as_str()));
+
+            Some(RevertPair {
+                original_hash: original.map_or_else(|| reverted_hash.clone(), |commit| commit.hash.clone()),
+                revert_hash: revert.hash.clone(),
+                resolved: original.is_some(),
+                time_to_revert_seconds: original.map(|commit| revert.timestamp - commit.timestamp),
+            })
+        })
+        .collect()
+}
+
+pub fn latest_per_author(mut commits: Vec<Commit>, limit: usize) -> Vec<Commit> {
+    commits.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.index.cmp(&b.index)));
+
+    let mut kept_by_author: HashMap<String, usize> = HashMap::new();
+    let mut kept: Vec<Commit> = commits.into_iter()
+        .filter(|commit| {
+            let count = kept_by_author.entry(commit.author_email.clone()).or_insert(0);
+            *count += 1;
+            *count <= limit
+        })
+        .collect();
+
+    kept.sort_by_key(|commit| commit.index);
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FileChange;
+
+    fn commit(timestamp: i64, files: &[(&str, u32, u32)]) -> Commit {
+        authored_commit("test@example.com", timestamp, files)
+    }
+
+    fn authored_commit(author_email: &str, timestamp: i64, files: &[(&str, u32, u32)]) -> Commit {
+        Commit {
+            hash: format!("{:040}", timestamp),
+            author_email: author_email.to_string(),
+            timestamp,
+            files: files.iter()
+                .map(|(filename, additions, deletions)| FileChange {
+                    filename: filename.to_string(),
+                    status: "M".to_string(),
+                    additions: *additions,
+                    deletions: *deletions,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_file_churn_without_decay() {
+        let commits = vec![
+            commit(0, &[("a.rs", 3, 1), ("b.rs", 1, 0)]),
+            commit(100, &[("a.rs", 2, 2)]),
+        ];
+
+        let churn = file_churn(&commits, None, 100);
+
+        assert_eq!(churn[0].filename, "a.rs");
+        assert_eq!(churn[0].churn, 8);
+        assert_eq!(churn[0].weighted_churn, 8.0);
+        assert_eq!(churn[0].net_lines, 2);
+        assert_eq!(churn[1].filename, "b.rs");
+        assert_eq!(churn[1].churn, 1);
+    }
+
+    #[test]
+    fn test_top_churned_files() {
+        let commits = vec![
+            commit(0, &[("a.rs", 3, 0), ("b.rs", 9, 0), ("c.rs", 5, 0), ("d.rs", 5, 0), ("e.rs", 1, 0)]),
+        ];
+
+        let top: Vec<String> = top_churned_files(&commits, 3).into_iter().map(|file| file.filename).collect();
+        assert_eq!(top, vec!["b.rs", "c.rs", "d.rs"]);
+        assert_eq!(top_churned_files(&commits, 10).len(), 5);
+        assert!(top_churned_files(&commits, 0).is_empty());
+    }
+
+    #[test]
+    fn test_file_churn_with_half_life() {
+        let now = 20 * SECONDS_PER_DAY as i64;
+        let commits = vec![
+            commit(now - 10 * SECONDS_PER_DAY as i64, &[("old.rs", 4, 0)]),
+            commit(now, &[("new.rs", 4, 0)]),
+        ];
+
+        let churn = file_churn(&commits, Some(10.0), now);
+        let old = churn.iter().find(|c| c.filename == "old.rs").unwrap();
+        let new = churn.iter().find(|c| c.filename == "new.rs").unwrap();
+
+        assert_eq!(old.churn, new.churn);
+        assert!((old.weighted_churn - 2.0).abs() < 1e-9);
+        assert!((new.weighted_churn - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_relative_churn() {
+        let commits = vec![
+            commit(0, &[("small.rs", 20, 10), ("big.rs", 100, 0), ("gone.rs", 5, 0)]),
+        ];
+        let line_counts = vec![
+            FileLineCount { filename: "small.rs".to_string(), lines: 10, binary: false },
+            FileLineCount { filename: "big.rs".to_string(), lines: 1000, binary: false },
+        ];
+
+        let relative = relative_churn(&file_churn(&commits, None, 0), &line_counts, 2.0);
+
+        assert_eq!(relative.len(), 2);
+        assert_eq!(relative[0].filename, "small.rs");
+        assert!((relative[0].relative_churn - 3.0).abs() < 1e-9);
+        assert!(relative[0].unstable);
+        assert_eq!(relative[1].filename, "big.rs");
+        assert!(!relative[1].unstable);
+    }
+
+    #[test]
+    fn test_primary_language() {
+        let line_count = |filename: &str, lines: usize, binary: bool| FileLineCount {
+            filename: filename.to_string(),
+            lines,
+            binary,
+        };
+        let line_counts = vec![
+            line_count("src/lib.rs", 300, false),
+            line_count("src/main.RS", 100, false),
+            line_count("scripts/build.py", 100, false),
+            line_count("README.md", 5000, false),
+            line_count("logo.png", 0, true),
+        ];
+
+        let primary = primary_language(&line_counts).unwrap();
+        assert_eq!(primary.language, "Rust");
+        assert_eq!((primary.lines, primary.total_lines), (400, 500));
+        assert!((primary.percentage - 80.0).abs() < 1e-9);
+
+        assert_eq!(primary_language(&[line_count("README.md", 10, false)]), None);
+        assert_eq!(primary_language(&[]), None);
+    }
+
+    #[test]
+    fn test_stale_files() {
+        let commits = vec![
+            commit(500, &[("fresh.rs", 1, 0)]),
+            commit(200, &[("old.rs", 1, 0), ("fresh.rs", 1, 0)]),
+            commit(100, &[("older.rs", 1, 0), ("deleted.rs", 0, 1)]),
+        ];
+        let tracked: Vec<String> = ["fresh.rs", "old.rs", "older.rs", "untouched.rs"].iter()
+            .map(|filename| filename.to_string())
+            .collect();
+
+        let stale: Vec<(String, Option<i64>)> = stale_files(&tracked, &commits, 1, 300 + SECONDS_PER_DAY as i64).into_iter()
+            .map(|file| (file.filename, file.last_modified))
+            .collect();
+        assert_eq!(stale, vec![
+            ("untouched.rs".to_string(), None),
+            ("older.rs".to_string(), Some(100)),
+            ("old.rs".to_string(), Some(200)),
+        ]);
+    }
+
+    #[test]
+    fn test_file_ownership() {
+        let commits = vec![
+            authored_commit("alice@example.com", 1, &[("a.rs", 1, 0), ("b.rs", 1, 0)]),
+            authored_commit("alice@example.com", 2, &[("a.rs", 1, 0)]),
+            authored_commit("bob@example.com", 3, &[("a.rs", 1, 0), ("b.rs", 1, 0)]),
+            authored_commit("bob@example.com", 4, &[("b.rs", 1, 0)]),
+        ];
+
+        let ownership = file_ownership(&commits);
+        let a = ownership.iter().find(|o| o.filename == "a.rs").unwrap();
+        let b = ownership.iter().find(|o| o.filename == "b.rs").unwrap();
+
+        assert_eq!(a.dominant_author, "alice@example.com");
+        assert!((a.ownership_ratio - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(a.contributor_count, 2);
+        assert_eq!(b.dominant_author, "bob@example.com");
+    }
+
+    #[test]
+    fn test_commits_touching_owned_files() {
+        let commits = vec![
+            authored_commit("alice@example.com", 1, &[("a.rs", 1, 0)]),
+            authored_commit("alice@example.com", 2, &[("a.rs", 1, 0)]),
+            authored_commit("bob@example.com", 3, &[("a.rs", 1, 0), ("c.rs", 1, 0)]),
+            authored_commit("bob@example.com", 4, &[("c.rs", 1, 0)]),
+        ];
+
+        let touched = commits_touching_owned_files(&commits, "alice@example.com");
+
+        assert_eq!(touched.len(), 1);
+        assert_eq!(touched[0].commit.timestamp, 3);
+        assert_eq!(touched[0].owned_files, vec!["a.rs"]);
+    }
+
+    #[test]
+    fn test_collaboration_graph() {
+        let commits = vec![
+            authored_commit("bob@example.com", 1, &[("a.rs", 1, 0), ("b.rs", 1, 0)]),
+            authored_commit("alice@example.com", 2, &[("a.rs", 1, 0), ("b.rs", 1, 0)]),
+            authored_commit("alice@example.com", 3, &[("a.rs", 1, 0)]),
+            authored_commit("carol@example.com", 4, &[("b.rs", 1, 0), ("c.rs", 1, 0)]),
+        ];
+
+        let edges = collaboration_graph(&commits);
+        let pairs: Vec<(&str, &str, u32)> = edges.iter()
+            .map(|edge| (edge.author_a.as_str(), edge.author_b.as_str(), edge.weight))
+            .collect();
+
+        assert_eq!(pairs, vec![
+            ("alice@example.com", "bob@example.com", 2),
+            ("alice@example.com", "carol@example.com", 1),
+            ("bob@example.com", "carol@example.com", 1),
+        ]);
+    }
+
+    #[test]
+    fn test_co_change_clusters() {
+        let commits = vec![
+            commit(1, &[("a.rs", 1, 0), ("b.rs", 1, 0)]),
+            commit(2, &[("a.rs", 1, 0), ("b.rs", 1, 0), ("c.rs", 1, 0)]),
+            commit(3, &[("b.rs", 1, 0), ("c.rs", 1, 0)]),
+            commit(4, &[("x.rs", 1, 0), ("y.rs", 1, 0)]),
+            commit(5, &[("x.rs", 1, 0)]),
+            commit(6, &[("x.rs", 1, 0)]),
+            commit(7, &[("lonely.rs", 1, 0)]),
+        ];
+
+        let clusters = co_change_clusters(&commits, 0.5, 2);
+        assert_eq!(clusters, vec![vec!["a.rs", "b.rs", "c.rs"]]);
+
+        let clusters = co_change_clusters(&commits, 0.3, 2);
+        assert_eq!(clusters, vec![vec!["a.rs", "b.rs", "c.rs"], vec!["x.rs", "y.rs"]]);
+
+        assert!(co_change_clusters(&commits, 0.3, 4).is_empty());
+    }
+
+    #[test]
+    fn test_commit_size_stats() {
+        let mut commits: Vec<Commit> = (1..=10)
+            .map(|size| commit(size as i64, &[("a.rs", size, 0)]))
+            .collect();
+        commits.push(Commit { parents: vec!["a".into(), "b".into()], ..commit(11, &[("a.rs", 500, 0)]) });
+        let mut binary = commit(12, &[("logo.png", 0, 0)]);
+        binary.files[0].binary = true;
+        commits.push(binary);
+
+        let all = commit_size_stats(&commits, false, false);
+        assert_eq!(all.commit_count, 12);
+        assert_eq!(all.max, 500);
+
+        let filtered = commit_size_stats(&commits, true, true);
+        assert_eq!(filtered, CommitSizeStats { commit_count: 10, mean: 5.5, max: 10, p50: 5, p90: 9, p99: 10 });
+
+        assert_eq!(commit_size_stats(&[], true, true), CommitSizeStats::default());
+    }
+
+    #[test]
+    fn test_commits_by_file() {
+        let mut rename = commit(2, &[("new.rs", 1, 1)]);
+        rename.files[0].status = "R090".to_string();
+        rename.files[0].old_filename = Some("old.rs".to_string());
+        let mut copy = commit(3, &[("copy.rs", 1, 0)]);
+        copy.files[0].status = "C100".to_string();
+        copy.files[0].old_filename = Some("new.rs".to_string());
+
+        let commits = vec![
+            commit(4, &[("new.rs", 1, 0)]),
+            copy,
+            rename,
+            commit(1, &[("old.rs", 5, 0), ("other.rs", 1, 0)]),
+        ];
+
+        let by_file = commits_by_file(&commits);
+        let hash = |timestamp: i64| format!("{:040}", timestamp);
+
+        assert_eq!(by_file["new.rs"], vec![hash(1), hash(2), hash(4)]);
+        assert_eq!(by_file["copy.rs"], vec![hash(1), hash(2), hash(3)]);
+        assert_eq!(by_file["other.rs"], vec![hash(1)]);
+        assert!(!by_file.contains_key("old.rs"));
+    }
+
+    #[test]
+    fn test_commit_cadence() {
+        let hour = SECONDS_PER_HOUR as i64;
+        let commits = vec![
+            authored_commit("alice@example.com", 10 * hour, &[]),
+            authored_commit("alice@example.com", 0, &[]),
+            authored_commit("alice@example.com", 2 * hour, &[]),
+            authored_commit("alice@example.com", 3 * hour, &[]),
+            authored_commit("bob@example.com", 5 * hour, &[]),
+        ];
+
+        let cadence = commit_cadence(&commits);
+
+        assert_eq!(cadence["alice@example.com"], CommitCadence { median_gap_hours: Some(2.0), commit_count: 4 });
+        assert_eq!(cadence["bob@example.com"], CommitCadence { median_gap_hours: None, commit_count: 1 });
+    }
+
+    #[test]
+    fn test_author_month_matrix() {
+        let jan = 1_704_067_200; // 2024-01-01T00:00:00Z
+        let day = SECONDS_PER_DAY as i64;
+        let commits = vec![
+            authored_commit("b@example.com", jan + 70 * day, &[("a.rs", 4, 1)]),
+            authored_commit("a@example.com", jan + 5 * day, &[("a.rs", 1, 0)]),
+            authored_commit("a@example.com", jan, &[("a.rs", 2, 2)]),
+        ];
+
+        let matrix = author_month_matrix(&commits, false);
+        assert_eq!(matrix.authors, vec!["a@example.com", "b@example.com"]);
+        assert_eq!(matrix.months, vec!["2024-01", "2024-02", "2024-03"]);
+        assert_eq!(matrix.counts, vec![vec![2, 0, 0], vec![0, 0, 1]]);
+
+        assert_eq!(author_month_matrix(&commits, true).counts, vec![vec![5, 0, 0], vec![0, 0, 5]]);
+        assert!(author_month_matrix(&[], false).months.is_empty());
+    }
+
+    #[test]
+    fn test_churn_velocity() {
+        let week = 7 * SECONDS_PER_DAY as i64;
+        let commits = vec![
+            commit(3 * week + 5, &[("a.rs", 6, 0)]),
+            commit(week + 1, &[("a.rs", 1, 1)]),
+            commit(week, &[("b.rs", 2, 0)]),
+        ];
+
+        let series = churn_velocity(&commits, 7, 2);
+        let churn: Vec<(i64, u32)> = series.iter().map(|point| (point.bucket_start / week, point.churn)).collect();
+        assert_eq!(churn, vec![(1, 4), (2, 0), (3, 6)]);
+        let velocity: Vec<f64> = series.iter().map(|point| point.velocity).collect();
+        assert_eq!(velocity, vec![4.0, 2.0, 3.0]);
+
+        assert!(churn_velocity(&commits, 0, 2).is_empty());
+        assert!(churn_velocity(&[], 7, 2).is_empty());
+    }
+
+    #[test]
+    fn test_growth_ratio_trend() {
+        let week = 7 * SECONDS_PER_DAY as i64;
+        let commits = vec![
+            commit(3 * week, &[("a.rs", 5, 5), ("b.rs", 1, 0)]),
+            commit(week + 1, &[("a.rs", 8, 2)]),
+            commit(week, &[("b.rs", 4, 0)]),
+        ];
+
+        let series = growth_ratio_trend(&commits, 7);
+        let points: Vec<(i64, u32, u32, Option<f64>)> = series.iter()
+            .map(|point| (point.bucket_start / week, point.additions, point.deletions, point.ratio))
+            .collect();
+        assert_eq!(points, vec![
+            (1, 12, 2, Some(6.0)),
+            (2, 0, 0, None),
+            (3, 6, 5, Some(1.2)),
+        ]);
+
+        assert!(growth_ratio_trend(&commits, 0).is_empty());
+        assert!(growth_ratio_trend(&[], 7).is_empty());
+    }
+
+    #[test]
+    fn test_extension_churn_trend() {
+        let month = 30 * SECONDS_PER_DAY as i64;
+        let commits = vec![
+            commit(2 * month, &[("app.ts", 50, 0), ("app.js", 0, 40)]),
+            commit(0, &[("app.js", 40, 0), ("Makefile", 2, 0)]),
+        ];
+
+        let trend = extension_churn_trend(&commits, 30);
+        let totals = |extension: &str| trend[extension].iter()
+            .map(|bucket| (bucket.additions, bucket.deletions))
+            .collect::<Vec<_>>();
+        assert_eq!(totals("js"), vec![(40, 0), (0, 0), (0, 40)]);
+        assert_eq!(totals("ts"), vec![(0, 0), (0, 0), (50, 0)]);
+        assert_eq!(totals(""), vec![(2, 0), (0, 0), (0, 0)]);
+        assert_eq!(trend["ts"][2].bucket_start, 2 * month);
+    }
+
+    #[test]
+    fn test_punchcard() {
+        let monday_23h = 1_704_150_000 - 20 * 60; // 2024-01-01T22:40:00Z, a Monday
+        let mut east = commit(monday_23h, &[("a.rs", 1, 0)]);
+        east.utc_offset_minutes = 120;
+        let commits = vec![east, commit(monday_23h + 3600, &[("a.rs", 1, 0)])];
+
+        let utc = punchcard(&commits, Some(0));
+        assert_eq!((utc[0][22], utc[0][23]), (1, 1));
+        assert_eq!(utc.iter().flatten().sum::<u32>(), 2);
+
+        let author_local = punchcard(&commits, None);
+        assert_eq!((author_local[1][0], author_local[0][23]), (1, 1));
+
+        let fixed = punchcard(&commits, Some(-60));
+        assert_eq!((fixed[0][21], fixed[0][22]), (1, 1));
+    }
+
+    #[test]
+    fn test_directory_truck_factor() {
+        let commits = vec![
+            authored_commit("a@example.com", 0, &[("src/core/a.rs", 1, 0), ("src/core/b.rs", 1, 0), ("README", 1, 0)]),
+            authored_commit("b@example.com", 1, &[("src/ui/view.rs", 1, 0), ("src/core/a.rs", 1, 0)]),
+            authored_commit("c@example.com", 2, &[("src/ui/view.rs", 1, 0), ("src/main.rs", 1, 0)]),
+        ];
+
+        let summary = |depth| directory_truck_factor(&commits, depth).into_iter()
+            .map(|dir| (dir.directory, dir.truck_factor, dir.high_risk))
+            .collect::<Vec<_>>();
+
+        assert_eq!(summary(1), vec![
+            (".".to_string(), 1, true),
+            ("src".to_string(), 2, false),
+        ]);
+        assert_eq!(summary(2), vec![
+            ("src/core".to_string(), 1, true),
+            (".".to_string(), 1, true),
+            ("src".to_string(), 1, true),
+            ("src/ui".to_string(), 2, false),
+        ]);
+    }
+
+    #[test]
+    fn test_directory_report() {
+        let commits = vec![
+            authored_commit("a@example.com", 10, &[("src/core/a.rs", 5, 5), ("README", 1, 0)]),
+            authored_commit("b@example.com", 30, &[("src/ui/view.rs", 2, 0), ("src/core/a.rs", 1, 0)]),
+            authored_commit("a@example.com", 20, &[("src/core/b.rs", 3, 0)]),
+        ];
+
+        let by_churn = directory_report(&commits, 1, DirectorySort::Churn);
+        assert_eq!(by_churn.len(), 2);
+        assert_eq!(by_churn[0].directory, "src");
+        assert_eq!(by_churn[0].dominant_author, "a@example.com");
+        assert!((by_churn[0].ownership_ratio - 0.5).abs() < 1e-9);
+        assert_eq!(by_churn[0].contributor_count, 2);
+        assert_eq!((by_churn[0].last_modified, by_churn[0].churn), (30, 16));
+        assert_eq!((by_churn[1].directory.as_str(), by_churn[1].last_modified, by_churn[1].churn), (".", 10, 1));
+
+        let by_staleness: Vec<(String, i64)> = directory_report(&commits, 2, DirectorySort::Staleness).into_iter()
+            .map(|dir| (dir.directory, dir.last_modified))
+            .collect();
+        assert_eq!(by_staleness, vec![
+            (".".to_string(), 10),
+            ("src/core".to_string(), 30),
+            ("src/ui".to_string(), 30),
+        ]);
+    }
+
+    #[test]
+    fn test_active_contributors() {
+        let day = SECONDS_PER_DAY as i64;
+        let commits = vec![
+            authored_commit("c@example.com", 10 * day, &[("a.rs", 1, 0)]),
+            authored_commit("a@example.com", 5 * day, &[("a.rs", 1, 0)]),
+            authored_commit("b@example.com", day, &[("a.rs", 1, 0)]),
+            authored_commit("a@example.com", 0, &[("a.rs", 1, 0)]),
+        ];
+
+        let series: Vec<(i64, usize)> = active_contributors(&commits, 3, 2).iter()
+            .map(|point| (point.period_end / day, point.active_authors))
+            .collect();
+        assert_eq!(series, vec![(0, 1), (2, 2), (4, 0), (6, 1), (8, 0), (10, 1)]);
+
+        assert!(active_contributors(&commits, 3, 0).is_empty());
+        assert!(active_contributors(&[], 3, 2).is_empty());
+    }
+
+    #[test]
+    fn test_resurrected_files() {
+        let with_status = |timestamp: i64, files: &[(&str, &str)]| {
+            let mut commit = commit(timestamp, &files.iter().map(|(name, _)| (*name, 1, 0)).collect::<Vec<_>>());
+            for (file, (_, status)) in commit.files.iter_mut().zip(files) {
+                file.status = status.to_string();
+            }
+            commit
+        };
+        let commits = vec![
+            with_status(5, &[("a.rs", "A")]),
+            with_status(4, &[("a.rs", "D")]),
+            with_status(3, &[("b.rs", "M")]),
+            with_status(2, &[("a.rs", "A"), ("b.rs", "D")]),
+            with_status(1, &[("a.rs", "D"), ("b.rs", "A")]),
+        ];
+
+        let hash = |timestamp: i64| format!("{:040}", timestamp);
+        assert_eq!(resurrected_files(&commits), vec![
+            ResurrectedFile { filename: "a.rs".to_string(), deleted_in: hash(1), readded_in: hash(2) },
+            ResurrectedFile { filename: "a.rs".to_string(), deleted_in: hash(4), readded_in: hash(5) },
+        ]);
+    }
+
+    #[test]
+    fn test_message_quality() {
+        let message_commit = |timestamp: i64, message: &str, body: &str| Commit {
+            message: message.to_string(),
+            body: body.to_string(),
+            ..commit(timestamp, &[])
+        };
+        let commits = vec![
+            message_commit(1, "feat(parser): support trailers", "Closes #12"),
+            message_commit(2, "wip", ""),
+            message_commit(3, "Fix crash when HEAD is unborn (PROJ-7)", ""),
+            message_commit(4, "fix!: this subject line is far too long for the configured maximum", "Details"),
+        ];
+        let rules = MessageRules {
+            subject_min_length: 10,
+            subject_max_length: 50,
+            issue_pattern: Regex::new(r"#\d+|\b[A-Z][A-Z0-9]+-\d+\b").unwrap(),
+        };
+
+        let (scores, distribution) = message_quality(&commits, &rules);
+        let flags: Vec<(bool, bool, bool, bool, usize)> = scores.iter()
+            .map(|quality| (quality.has_body, quality.subject_length_ok, quality.references_issue, quality.conventional, quality.score))
+            .collect();
+        assert_eq!(flags, vec![
+            (true, true, true, true, 4),
+            (false, false, false, false, 0),
+            (false, true, true, false, 2),
+            (true, false, false, true, 2),
+        ]);
+        assert_eq!(distribution, vec![1, 0, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_test_discipline() {
+        let commits = vec![
+            commit(1, &[("src/lib.rs", 1, 0)]),
+            commit(2, &[("src/lib.rs", 1, 0), ("tests/lib.rs", 1, 0)]),
+            commit(3, &[("tests/lib.rs", 1, 0)]),
+            commit(4, &[("README.md", 1, 0)]),
+            commit(5, &[("src/parser.rs", 1, 0), ("docs/guide.md", 1, 0)]),
+        ];
+
+        let discipline = test_discipline(&commits, |file| file.starts_with("tests/"), |file| file.ends_with(".rs"));
+        let categories: Vec<TestCategory> = discipline.categories.iter().map(|(_, category)| *category).collect();
+        assert_eq!(categories, vec![
+            TestCategory::CodeOnly,
+            TestCategory::CodeAndTests,
+            TestCategory::TestsOnly,
+            TestCategory::Neither,
+            TestCategory::CodeOnly,
+        ]);
+        assert_eq!((discipline.code_only, discipline.tests_only, discipline.code_and_tests, discipline.neither), (2, 1, 1, 1));
+        assert!((discipline.test_ratio.unwrap() - 1.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(test_discipline(&commits[3..4], |_| false, |_| false).test_ratio, None);
+    }
+
+    #[test]
+    fn test_review_stats() {
+        let reviewed = |author: &str, timestamp: i64, trailers: &[(&str, &str)]| {
+            let mut commit = authored_commit(author, timestamp, &[]);
+            for (key, value) in trailers {
+                commit.trailers.entry(key.to_string()).or_default().push(value.to_string());
+            }
+            commit
+        };
+        let commits = vec![
+            reviewed("a@example.com", 1, &[("Reviewed-by", "Bob <Bob@Example.com>"), ("reviewed-by", "Carol <carol@example.com>")]),
+            reviewed("a@example.com", 2, &[("Reviewed-by", "Bob <bob@example.com>"), ("Reviewed-By", "Bob <bob@example.com>")]),
+            reviewed("a@example.com", 3, &[("Signed-off-by", "A <a@example.com>")]),
+            reviewed("b@example.com", 4, &[("Reviewed-by", "release-team")]),
+        ];
+
+        let stats = review_stats(&commits);
+        let coverage: Vec<(&str, usize, usize)> = stats.coverage.iter()
+            .map(|author| (author.author.as_str(), author.commits, author.reviewed_commits))
+            .collect();
+        assert_eq!(coverage, vec![("a@example.com", 3, 2), ("b@example.com", 1, 1)]);
+        assert!((stats.coverage[0].coverage - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.reviewers, vec![
+            ("bob@example.com".to_string(), 2),
+            ("carol@example.com".to_string(), 1),
+            ("release-team".to_string(), 1),
+        ]);
+    }
+
+    #[test]
+    fn test_revert_pairs() {
+        let original = Commit { hash: "a".repeat(40), timestamp: 100, ..Default::default() };
+        let revert = Commit {
+            hash: "b".repeat(40),
+            timestamp: 160,
+            reverted_hash: Some("a".repeat(40)),
+            ..Default::default()
+        };
+        let dangling = Commit {
+            hash: "c".repeat(40),
+            timestamp: 200,
+            reverted_hash: Some("d".repeat(40)),
+            ..Default::default()
+        };
+
+        let pairs = revert_pairs(&[dangling, revert, original]);
+
+        assert_eq!(pairs.len(), 2);
+        assert!(!pairs[0].resolved);
+        assert_eq!(pairs[0].original_hash, "d".repeat(40));
+        assert_eq!(pairs[0].time_to_revert_seconds, None);
+        assert!(pairs[1].resolved);
+        assert_eq!(pairs[1].time_to_revert_seconds, Some(60));
+    }
+
+    #[test]
+    fn test_latest_per_author() {
+        let commits: Vec<Commit> = [
+            ("alice@example.com", 50),
+            ("bob@example.com", 40),
+            ("alice@example.com", 30),
+            ("alice@example.com", 20),
+            ("bob@example.com", 10),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, (author, timestamp))| Commit {
+            index,
+            ..authored_commit(author, *timestamp, &[])
+        })
+        .collect();
+
+        let kept = latest_per_author(commits, 2);
+        let timestamps: Vec<i64> = kept.iter().map(|commit| commit.timestamp).collect();
+
+        assert_eq!(timestamps, vec![50, 40, 30, 10]);
+    }
+}
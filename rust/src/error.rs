@@ -8,9 +8,15 @@ pub enum GitMetricsError {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
     
+    #[error("Binary serialization error: {0}")]
+    BinarySerializationError(#[from] bincode::Error),
+    
     #[error("Command error: {0}")]
     CommandError(String),
     
+    #[error("Malformed commit {hash}: {reason}")]
+    MalformedCommit { hash: String, reason: String },
+    
     #[error("{0}")]
     Other(String),
 }
@@ -1,19 +1,63 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
 use std::time::{Duration, SystemTime};
-use chrono::{Utc, TimeZone};
-use log::{debug, info};
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use encoding_rs::Encoding;
+use log::{debug, info, warn};
 use rayon::prelude::*;
 use regex::Regex;
 
+use crate::analysis;
 use crate::error::{GitMetricsError, Result};
-use crate::models::{Commit, FileChange};
+use crate::models::{
+    BranchDivergence, CacheFormat, CacheStatus, Commit, DiffMode, DiffSummary, FileChange, FileLineCount, ReflogEntry, TagRange,
+    TraversalOrder,
+};
 
 const CACHE_TTL_SECONDS: u64 = 86400;
-const COMMIT_START_MARKER: &str = "COMMIT_START\n";
+const CACHE_SCHEMA_VERSION: u32 = 11;
+const COMMIT_START_MARKER: &str = "COMMIT_START";
 const COMMIT_END_MARKER: &str = "COMMIT_END";
+const HEADER_LINES: usize = 10;
+const SIGNATURE_LINES: usize = 3;
+const BINARY_SNIFF_BYTES: usize = 8000;
+const GIT_CONFIG_OVERRIDES: &[&str] = &[
+    "core.pager=cat", "color.ui=false", "log.showSignature=false",
+    "advice.detachedHead=false", "advice.statusHints=false", "advice.waitingForEditor=false",
+];
+const SCRUBBED_ENV_VARS: &[&str] = &[
+    "GIT_PAGER", "PAGER", "GIT_EXTERNAL_DIFF", "GIT_DIFF_OPTS", "GIT_TRACE", "GIT_TRACE_PACKET",
+    "GIT_TRACE_PERFORMANCE", "GIT_TRACE_SETUP", "GIT_TRACE2", "GIT_TRACE2_EVENT", "GIT_TRACE2_PERF",
+];
+// --date=iso-strict and %aI need git 2.2; --date=unix needs 2.9.
+const MIN_GIT_VERSION: (u32, u32, u32) = (2, 2, 0);
+const UNIX_DATE_GIT_VERSION: (u32, u32, u32) = (2, 9, 0);
+const DEFAULT_CODE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "pyx", "js", "jsx", "ts", "tsx", "go", "java", "kt", "scala", "c", "h", "cc", "cpp",
+    "hpp", "cs", "rb", "php", "swift", "m", "sh", "sql", "lua", "ex", "exs", "hs", "clj", "dart",
+];
+const DEFAULT_TEST_PATTERNS: &[&str] = &[
+    "tests/**", "test/**", "**/tests/**", "**/test/**", "__tests__/**", "**/__tests__/**",
+    "*_test.*", "test_*.*", "*.test.*", "*.spec.*",
+];
+const SYMLINK_MODE: &str = "120000";
+const RESERVED_LOG_ARGS: &[&str] = &[
+    "--pretty", "--format", "--oneline", "--name-status", "--name-only",
+    "--stat", "--numstat", "--shortstat", "--raw", "--patch", "-p", "-z",
+    "--no-abbrev", "--graph", "-s", "--no-patch", "-u", "--patch-with-stat", "--patch-with-raw",
+    "--show-signature",
+];
+
+/// Each `exclude_authors` entry with its compiled regex when it carries the `re:` prefix.
+type AuthorMatchers = Vec<(String, Option<Regex>)>;
 
 pub struct GitCollector {
     repo_path: String,
@@ -21,9 +65,60 @@ pub struct GitCollector {
     since_days: Option<u32>,
     cache_dir: PathBuf,
     file_patterns: Vec<String>,
+    fail_on_shallow: bool,
+    extra_log_args: Vec<String>,
+    abbrev_length: Option<u8>,
+    use_mailmap: Option<bool>,
+    normalize_rename_churn: bool,
+    max_commits_per_author: Option<usize>,
+    record_commands: bool,
+    last_command: Arc<Mutex<Option<String>>>,
+    since_last_tag: bool,
+    include_merge_diffs: bool,
+    max_output_bytes: Option<usize>,
+    exclude_authors: Vec<String>,
+    detect_renames: Option<bool>,
+    detect_copies: bool,
+    rename_threshold: Option<u8>,
+    status_filter: Option<Vec<char>>,
+    cache_format: CacheFormat,
+    date_format: Option<String>,
+    portable_cache_key: bool,
+    min_files: Option<usize>,
+    include_empty: bool,
+    strip_path_prefix: Option<String>,
+    code_extensions: Option<Vec<String>>,
+    hard_commit_cap: Option<usize>,
+    traversal_order: Option<TraversalOrder>,
+    author_aliases: HashMap<String, String>,
+    pathspecs: Vec<String>,
+    require_clean: bool,
+    capture_signatures: bool,
+    capture_encoding: bool,
+    git_dir: Option<String>,
+    work_tree: Option<String>,
+    max_file_lines: Option<u32>,
+    follow_path: Option<String>,
+    detect_whitespace_only: bool,
+    fast_path: bool,
+    record_markers: Option<(String, String)>,
+    ref_name: Option<String>,
+    since_date: Option<String>,
+    until_date: Option<String>,
+    ascending: bool,
+    extensions: Vec<String>,
+    exclude_paths: Vec<String>,
+    author_email: Option<String>,
+    pattern_hits: Vec<AtomicBool>,
+    exclude_author_patterns: OnceLock<std::result::Result<AuthorMatchers, String>>,
+    git_version: OnceLock<Option<(u32, u32, u32)>>,
 }
 
 impl GitCollector {
+    /// `file_patterns` match the whole repository-relative path: `*` stays within one directory,
+    /// `**` spans directories (`**/` also matches none), a trailing `/` matches everything under
+    /// that directory, and other patterns must match exactly. `*.rs` only matches top-level
+    /// files; `**/*.rs` matches at any depth.
     pub fn new(
         repo_path: &str, 
         max_commits: Option<u32>, 
@@ -37,53 +132,582 @@ impl GitCollector {
             let _ = fs::create_dir_all(&cache_dir);
         }
         
+        let pattern_hits = file_patterns.iter().map(|_| AtomicBool::new(false)).collect();
+        
         GitCollector {
             repo_path: repo_path.to_string(),
             max_commits,
             since_days,
             cache_dir,
             file_patterns,
+            fail_on_shallow: false,
+            extra_log_args: Vec::new(),
+            abbrev_length: None,
+            use_mailmap: None,
+            normalize_rename_churn: false,
+            max_commits_per_author: None,
+            record_commands: false,
+            last_command: Arc::new(Mutex::new(None)),
+            since_last_tag: false,
+            include_merge_diffs: false,
+            max_output_bytes: None,
+            exclude_authors: Vec::new(),
+            detect_renames: None,
+            detect_copies: false,
+            rename_threshold: None,
+            status_filter: None,
+            cache_format: CacheFormat::Json,
+            date_format: None,
+            portable_cache_key: false,
+            min_files: None,
+            include_empty: false,
+            strip_path_prefix: None,
+            code_extensions: None,
+            hard_commit_cap: None,
+            traversal_order: None,
+            author_aliases: HashMap::new(),
+            pathspecs: Vec::new(),
+            require_clean: false,
+            capture_signatures: false,
+            capture_encoding: false,
+            git_dir: None,
+            work_tree: None,
+            max_file_lines: None,
+            follow_path: None,
+            detect_whitespace_only: false,
+            fast_path: false,
+            record_markers: None,
+            ref_name: None,
+            since_date: None,
+            until_date: None,
+            ascending: false,
+            extensions: Vec::new(),
+            exclude_paths: Vec::new(),
+            author_email: None,
+            pattern_hits,
+            exclude_author_patterns: OnceLock::new(),
+            git_version: OnceLock::new(),
+        }
+    }
+
+    pub fn read_patterns_file(path: &str) -> Result<Vec<String>> {
+        let contents = fs::read_to_string(path)
+            .map_err(GitMetricsError::IoError)?;
+        
+        Ok(contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect())
+    }
+    
+    pub fn with_fail_on_shallow(mut self, fail_on_shallow: bool) -> Self {
+        self.fail_on_shallow = fail_on_shallow;
+        self
+    }
+    
+    /// Extra arguments are passed verbatim to `git log` after the generated ones.
+    /// Anything that changes the output format would break parsing, so those
+    /// flags are rejected when collecting.
+    pub fn with_extra_log_args(mut self, extra_log_args: Vec<String>) -> Self {
+        self.extra_log_args = extra_log_args;
+        self
+    }
+    
+    pub fn with_abbrev_length(mut self, abbrev_length: Option<u8>) -> Self {
+        self.abbrev_length = abbrev_length;
+        self
+    }
+    
+    pub fn with_use_mailmap(mut self, use_mailmap: Option<bool>) -> Self {
+        self.use_mailmap = use_mailmap;
+        self
+    }
+    
+    pub fn with_normalize_rename_churn(mut self, normalize_rename_churn: bool) -> Self {
+        self.normalize_rename_churn = normalize_rename_churn;
+        self
+    }
+    
+    /// Needs the whole history to pick each author's latest commits, so streaming rejects it.
+    pub fn with_max_commits_per_author(mut self, max_commits_per_author: Option<usize>) -> Self {
+        self.max_commits_per_author = max_commits_per_author;
+        self
+    }
+    
+    /// Off by default so arguments that may be sensitive aren't kept around.
+    pub fn with_record_commands(mut self, record_commands: bool) -> Self {
+        self.record_commands = record_commands;
+        self
+    }
+    
+    pub fn with_command_log(mut self, last_command: Arc<Mutex<Option<String>>>) -> Self {
+        self.last_command = last_command;
+        self
+    }
+    
+    pub fn with_since_last_tag(mut self, since_last_tag: bool) -> Self {
+        self.since_last_tag = since_last_tag;
+        self
+    }
+    
+    /// Lists the files a merge changed relative to every parent (`--cc`). git's numstat for such
+    /// merges only covers the first parent, so their counts come from the combined patch instead.
+    pub fn with_include_merge_diffs(mut self, include_merge_diffs: bool) -> Self {
+        self.include_merge_diffs = include_merge_diffs;
+        self
+    }
+    
+    pub fn with_max_output_bytes(mut self, max_output_bytes: Option<usize>) -> Self {
+        self.max_output_bytes = max_output_bytes;
+        self
+    }
+    
+    /// Drops commits whose author name or email contains any entry as a plain substring, so
+    /// "dependabot[bot]" works as written. Prefix an entry with `re:` to match it as a regex.
+    pub fn with_exclude_authors(mut self, exclude_authors: Vec<String>) -> Self {
+        self.exclude_authors = exclude_authors;
+        self
+    }
+    
+    pub fn with_detect_renames(mut self, detect_renames: Option<bool>) -> Self {
+        self.detect_renames = detect_renames;
+        self
+    }
+    
+    pub fn with_detect_copies(mut self, detect_copies: bool) -> Self {
+        self.detect_copies = detect_copies;
+        self
+    }
+    
+    pub fn with_rename_threshold(mut self, rename_threshold: Option<u8>) -> Self {
+        self.rename_threshold = rename_threshold;
+        self
+    }
+    
+    pub fn with_status_filter(mut self, status_filter: Option<Vec<char>>) -> Self {
+        self.status_filter = status_filter;
+        self
+    }
+    
+    pub fn with_cache_format(mut self, cache_format: CacheFormat) -> Self {
+        self.cache_format = cache_format;
+        self
+    }
+    
+    pub fn with_date_format(mut self, date_format: Option<String>) -> Self {
+        self.date_format = date_format;
+        self
+    }
+    
+    pub fn with_portable_cache_key(mut self, portable_cache_key: bool) -> Self {
+        self.portable_cache_key = portable_cache_key;
+        self
+    }
+    
+    pub fn with_min_files(mut self, min_files: Option<usize>) -> Self {
+        self.min_files = min_files;
+        self
+    }
+    
+    pub fn with_include_empty(mut self, include_empty: bool) -> Self {
+        self.include_empty = include_empty;
+        self
+    }
+    
+    pub fn with_strip_path_prefix(mut self, strip_path_prefix: Option<String>) -> Self {
+        self.strip_path_prefix = strip_path_prefix;
+        self
+    }
+    
+    pub fn with_code_extensions(mut self, code_extensions: Option<Vec<String>>) -> Self {
+        self.code_extensions = code_extensions;
+        self
+    }
+    
+    /// Stops `stream_commits` after this many commits and kills git, whatever `max_commits` or
+    /// extra log args asked git for. Unlike `max_commits` it is enforced on our side of the pipe.
+    pub fn with_hard_commit_cap(mut self, hard_commit_cap: Option<usize>) -> Self {
+        self.hard_commit_cap = hard_commit_cap;
+        self
+    }
+    
+    /// `None` keeps git's default: reverse chronological by committer date, which can interleave
+    /// parallel branches. `Date` and `AuthorDate` never show a parent before all its children and
+    /// otherwise sort by committer or author timestamp; `Topo` also keeps each line of history
+    /// together. All three may make git walk much of the graph before printing anything, so on
+    /// big repos without a commit-graph file even a small `max_commits` gets noticeably slower.
+    pub fn with_traversal_order(mut self, traversal_order: Option<TraversalOrder>) -> Self {
+        self.traversal_order = traversal_order;
+        self
+    }
+    
+    /// Maps author emails to a canonical email at parse time, after mailmap, so every analysis
+    /// and `exclude_authors` see the canonical identity.
+    pub fn with_author_aliases(mut self, author_aliases: HashMap<String, String>) -> Self {
+        self.author_aliases = author_aliases;
+        self
+    }
+    
+    /// Raw git pathspecs, magic included (e.g. `:(exclude)vendor/**`), passed after `--` so git
+    /// limits the walk itself. They apply on top of `file_patterns`, which we still match here.
+    pub fn with_pathspecs(mut self, pathspecs: Vec<String>) -> Self {
+        self.pathspecs = pathspecs;
+        self
+    }
+    
+    pub fn with_require_clean(mut self, require_clean: bool) -> Self {
+        self.require_clean = require_clean;
+        self
+    }
+    
+    /// Fills `signature_status` (`%G?`), `signer` and `signing_key`. Off by default because git
+    /// has to verify every signature, which runs gpg or ssh-keygen once per signed commit.
+    pub fn with_capture_signatures(mut self, capture_signatures: bool) -> Self {
+        self.capture_signatures = capture_signatures;
+        self
+    }
+    
+    /// Records each commit's declared encoding (`%e`). Git re-encodes messages to UTF-8 itself when
+    /// iconv knows the encoding; messages it has to pass through raw are decoded here instead.
+    pub fn with_capture_encoding(mut self, capture_encoding: bool) -> Self {
+        self.capture_encoding = capture_encoding;
+        self
+    }
+    
+    /// Points git at a `.git` directory kept apart from the checkout, like `GIT_DIR`. Relative
+    /// paths resolve against `repo_path`, which is still where git runs.
+    pub fn with_git_dir(mut self, git_dir: Option<String>) -> Self {
+        self.git_dir = git_dir;
+        self
+    }
+    
+    /// The checkout belonging to `git_dir`, like `GIT_WORK_TREE`; only valid together with it.
+    pub fn with_work_tree(mut self, work_tree: Option<String>) -> Self {
+        self.work_tree = work_tree;
+        self
+    }
+    
+    /// Clamp per-file additions and deletions to this many lines so generated artifacts
+    /// don't swamp churn; the unclamped numstat stays in `raw_additions`/`raw_deletions`.
+    pub fn with_max_file_lines(mut self, max_file_lines: Option<u32>) -> Self {
+        self.max_file_lines = max_file_lines;
+        self
+    }
+    
+    /// Limit history to one file under every name it has had: the path's renames are resolved
+    /// with `--follow` up front and each former name becomes a literal pathspec.
+    pub fn with_follow_path(mut self, follow_path: Option<String>) -> Self {
+        self.follow_path = follow_path;
+        self
+    }
+    
+    /// Set `is_whitespace_only` on collected commits by re-running the log with `-w`. Costs a
+    /// second walk, and only applies to `collect_history`, not `stream_commits`.
+    pub fn with_detect_whitespace_only(mut self, detect_whitespace_only: bool) -> Self {
+        self.detect_whitespace_only = detect_whitespace_only;
+        self
+    }
+    
+    /// Trust the caller that the repository is valid: skips the shallow-clone, git version and
+    /// `git_dir`/`work_tree` checks, and keys the cache on paths as given rather than
+    /// canonicalized. Saves round-trips on slow filesystems, but misconfigurations surface as
+    /// git errors (or not at all) and differently spelled paths get separate cache entries.
+    pub fn with_fast_path(mut self, fast_path: bool) -> Self {
+        self.fast_path = fast_path;
+        self
+    }
+    
+    /// Start and end lines that frame each record in `git log` output, in place of
+    /// `COMMIT_START`/`COMMIT_END`, for histories whose messages contain those lines.
+    pub fn with_record_markers(mut self, record_markers: Option<(String, String)>) -> Self {
+        self.record_markers = record_markers;
+        self
+    }
+    
+    /// Walk this branch, tag or commit instead of HEAD. With `since_last_tag` the range becomes
+    /// `<last tag reachable from ref>..<ref>`.
+    pub fn with_ref_name(mut self, ref_name: Option<String>) -> Self {
+        self.ref_name = ref_name;
+        self
+    }
+    
+    pub fn with_since_days(mut self, since_days: Option<u32>) -> Self {
+        self.since_days = since_days;
+        self
+    }
+    
+    /// Lower commit-date bound in any form `git log --since` accepts; conflicts with
+    /// `since_days`.
+    pub fn with_since_date(mut self, since_date: Option<String>) -> Self {
+        self.since_date = since_date;
+        self
+    }
+    
+    /// Upper commit-date bound in any form `git log --until` accepts.
+    pub fn with_until_date(mut self, until_date: Option<String>) -> Self {
+        self.until_date = until_date;
+        self
+    }
+    
+    /// Oldest commit first (`--reverse`), so `index` 0 is the oldest. git limits before it
+    /// reverses: with `max_commits` this is still the newest N, just in ascending order, and git
+    /// buffers the whole walk before emitting anything, even when streaming.
+    pub fn with_ascending(mut self, ascending: bool) -> Self {
+        self.ascending = ascending;
+        self
+    }
+    
+    /// Limits collection to files with these extensions (`"rs"` or `".rs"`) at any depth, like the
+    /// file pattern `**/*.rs`, matched case-sensitively. Each becomes a `*.ext` pathspec as well.
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = extensions.into_iter()
+            .map(|extension| extension.trim_start_matches('.').to_string())
+            .collect();
+        self
+    }
+    
+    /// Globs (same syntax as `file_patterns`) for paths to drop; applied after the include
+    /// patterns, so a path matching both is excluded.
+    pub fn with_exclude_paths(mut self, exclude_paths: Vec<String>) -> Self {
+        self.exclude_paths = exclude_paths;
+        self
+    }
+    
+    /// Keeps only commits by `author_email` (or an alias of it), matched case-insensitively by
+    /// git's `--author` so `max_commits` counts that author's commits alone.
+    pub fn with_author_email(mut self, author_email: Option<String>) -> Self {
+        self.author_email = author_email;
+        self
+    }
+    
+    fn mailmap_enabled(&self) -> bool {
+        let checkout = Path::new(&self.repo_path).join(self.work_tree.as_deref().unwrap_or(""));
+        self.use_mailmap
+            .unwrap_or_else(|| checkout.join(".mailmap").exists())
+    }
+    
+    fn start_marker(&self) -> &str {
+        self.record_markers.as_ref().map_or(COMMIT_START_MARKER, |(start, _)| start.as_str())
+    }
+    
+    fn end_marker(&self) -> &str {
+        self.record_markers.as_ref().map_or(COMMIT_END_MARKER, |(_, end)| end.as_str())
+    }
+    
+    // Records are split on the start marker line, newline included.
+    fn record_separator(&self) -> String {
+        format!("{}\n", self.start_marker())
+    }
+    
+    // Markers are pasted into the format string, so `%` would read as a placeholder.
+    fn validate_exclude_authors(&self) -> Result<()> {
+        self.exclude_author_matchers().map(|_| ())
+    }
+    
+    fn exclude_author_matchers(&self) -> Result<&[(String, Option<Regex>)]> {
+        let matchers = self.exclude_author_patterns.get_or_init(|| {
+            self.exclude_authors.iter()
+                .map(|entry| match entry.strip_prefix("re:") {
+                    Some(pattern) => Regex::new(pattern)
+                        .map(|regex| (entry.clone(), Some(regex)))
+                        .map_err(|e| format!("Invalid exclude_authors regex '{}': {}", pattern, e)),
+                    None => Ok((entry.clone(), None)),
+                })
+                .collect()
+        });
+        matchers.as_deref().map_err(|message| GitMetricsError::Other(message.clone()))
+    }
+    
+    fn validate_markers(&self) -> Result<()> {
+        let (start, end) = (self.start_marker(), self.end_marker());
+        for marker in [start, end] {
+            if marker.is_empty() || !marker.chars().all(|c| c.is_ascii_alphanumeric() || "_-:.#".contains(c)) {
+                return Err(GitMetricsError::Other(format!(
+                    "Invalid record marker '{}': use ASCII letters, digits and _-:.#", marker.escape_debug()
+                )));
+            }
         }
+        if start == end {
+            return Err(GitMetricsError::Other("Record start and end markers must differ".to_string()));
+        }
+        Ok(())
+    }
+    
+    // Pinned by default so `commit.date` doesn't depend on the user's log.date config.
+    fn date_format(&self) -> &str {
+        self.date_format.as_deref().unwrap_or("iso-strict")
+    }
+    
+    fn author_placeholders(&self) -> (&'static str, &'static str) {
+        if self.mailmap_enabled() {
+            ("%aN", "%aE")
+        } else {
+            ("%an", "%ae")
+        }
+    }
+    
+    fn log_format(&self) -> String {
+        let (name, email) = self.author_placeholders();
+        let encoding = if self.capture_encoding { "%e%n" } else { "" };
+        let signature = if self.capture_signatures { "%G?%n%GS%n%GK%n" } else { "" };
+        format!(
+            "--pretty=format:{}%n%H%n%h%n{}%n{}%n%ad%n%at%n%aI%n%P%n%T%n{}{}%s%n%b%n{}",
+            self.start_marker(), name, email, encoding, signature, self.end_marker()
+        )
+    }
+    
+    // The subject is the last header line, after whichever optional fields are captured.
+    fn header_len(&self) -> usize {
+        let signature_lines = if self.capture_signatures { SIGNATURE_LINES } else { 0 };
+        HEADER_LINES + usize::from(self.capture_encoding) + signature_lines
+    }
+    
+    fn record_decoder(&self) -> RecordDecoder {
+        RecordDecoder {
+            encoding_line: self.capture_encoding.then_some(HEADER_LINES - 1),
+            message_line: self.header_len() - 1,
+            position: None,
+            encoding: None,
+            start_marker: self.record_separator(),
+            end_marker: self.end_marker().to_string(),
+        }
+    }
+    
+    fn raw_log_format(&self) -> String {
+        let (name, email) = self.author_placeholders();
+        format!("--pretty=format:%H%x00%h%x00{}%x00{}%x00%at%x00%aI%x00%s%x00%b", name, email)
     }
     
     fn get_cache_key(&self) -> Result<String> {
-        let repo_abs_path = fs::canonicalize(&self.repo_path)
-            .unwrap_or_else(|_| Path::new(&self.repo_path).to_path_buf());
-        
-        let max_commits_str = self.max_commits
-            .map_or_else(|| "all".to_string(), |n| n.to_string());
-        
-        let since_days_str = self.since_days
-            .map_or_else(|| "all".to_string(), |n| n.to_string());
+        let repo_identity = match self.root_commits()? {
+            Some(roots) => format!("roots:{}", roots.join(",")),
+            _ if self.fast_path => self.repo_path.clone(),
+            _ => fs::canonicalize(&self.repo_path)
+                .unwrap_or_else(|_| Path::new(&self.repo_path).to_path_buf())
+                .display()
+                .to_string(),
+        };
         
-        let patterns_str = if self.file_patterns.is_empty() {
-            "all".to_string()
+        let rev_range_str = if self.since_last_tag {
+            let tip = self.tip()?;
+            self.last_tag()?.map_or_else(|| "untagged".to_string(), |tag| format!("{}..{}", tag, tip))
         } else {
-            self.file_patterns.join(",")
+            "all".to_string()
         };
         
-        let key_str = format!("{}_{}_{}_{}",
-            repo_abs_path.display(),
-            max_commits_str, 
-            since_days_str,
-            patterns_str
-        );
+        // Every option that changes the collected commits must appear here;
+        // Debug formatting keeps list-valued options unambiguous.
+        let key_parts = [
+            ("schema", CACHE_SCHEMA_VERSION.to_string()),
+            ("repo", repo_identity),
+            ("max_commits", format!("{:?}", self.max_commits)),
+            ("since_days", format!("{:?}", self.since_days)),
+            ("file_patterns", format!("{:?}", self.file_patterns)),
+            ("extra_log_args", format!("{:?}", self.extra_log_args)),
+            ("abbrev_length", format!("{:?}", self.abbrev_length)),
+            ("mailmap", self.mailmap_enabled().to_string()),
+            ("normalize_rename_churn", self.normalize_rename_churn.to_string()),
+            ("rev_range", rev_range_str),
+            ("include_merge_diffs", self.include_merge_diffs.to_string()),
+            ("detect_renames", format!("{:?}", self.detect_renames)),
+            ("detect_copies", self.detect_copies.to_string()),
+            ("rename_threshold", format!("{:?}", self.rename_threshold)),
+            ("status_filter", format!("{:?}", self.status_filter)),
+            ("date_format", self.date_format().to_string()),
+            ("min_files", format!("{:?}", self.min_files)),
+            ("include_empty", self.include_empty.to_string()),
+            ("strip_path_prefix", format!("{:?}", self.strip_path_prefix)),
+            ("code_extensions", format!("{:?}", self.code_extensions)),
+            ("traversal_order", format!("{:?}", self.traversal_order)),
+            ("author_aliases", format!("{:?}", self.author_aliases.iter().collect::<BTreeMap<_, _>>())),
+            ("pathspecs", format!("{:?}", self.pathspecs)),
+            ("capture_signatures", self.capture_signatures.to_string()),
+            ("capture_encoding", self.capture_encoding.to_string()),
+            ("git_dir", format!("{:?}", self.git_dir.as_deref().map(|dir| self.canonical_path(dir)))),
+            ("work_tree", format!("{:?}", self.work_tree.as_deref().map(|dir| self.canonical_path(dir)))),
+            ("max_file_lines", format!("{:?}", self.max_file_lines)),
+            ("follow_path", format!("{:?}", self.follow_path)),
+            ("detect_whitespace_only", self.detect_whitespace_only.to_string()),
+            ("ref_name", format!("{:?}", self.ref_name)),
+            ("since_date", format!("{:?}", self.since_date)),
+            ("until_date", format!("{:?}", self.until_date)),
+            ("ascending", self.ascending.to_string()),
+            ("extensions", format!("{:?}", self.extensions)),
+            ("exclude_paths", format!("{:?}", self.exclude_paths)),
+            ("exclude_authors", format!("{:?}", self.exclude_authors)),
+            ("author_email", format!("{:?}", self.author_email)),
+        ];
+        
+        let key_str = key_parts.iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("\n");
         
         let digest = md5::compute(key_str.as_bytes());
         Ok(format!("{:x}", digest))
     }
     
+    fn canonical_path(&self, path: &str) -> String {
+        let path = Path::new(&self.repo_path).join(path);
+        if self.fast_path {
+            return path.display().to_string();
+        }
+        fs::canonicalize(&path).unwrap_or(path).display().to_string()
+    }
+    
+    fn check_repository_location(&self) -> Result<()> {
+        if self.fast_path {
+            return Ok(());
+        }
+        if self.work_tree.is_some() && self.git_dir.is_none() {
+            return Err(GitMetricsError::Other("work_tree requires git_dir to be set as well".to_string()));
+        }
+        
+        for (option, dir) in [("git_dir", &self.git_dir), ("work_tree", &self.work_tree)] {
+            if let Some(dir) = dir {
+                if !Path::new(&self.repo_path).join(dir).is_dir() {
+                    return Err(GitMetricsError::Other(format!("{} '{}' is not a directory", option, dir)));
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    fn root_commits(&self) -> Result<Option<Vec<String>>> {
+        if !self.portable_cache_key {
+            return Ok(None);
+        }
+        
+        match self.run_git_command(&["rev-list", "--max-parents=0", "HEAD"]) {
+            Ok(output) => {
+                let mut roots: Vec<String> = output.lines().map(str::to_string).collect();
+                roots.sort();
+                Ok(Some(roots).filter(|roots| !roots.is_empty()))
+            },
+            Err(GitMetricsError::CommandError(e)) => {
+                debug!("No root commit to key the cache on, using the path: {}", e);
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+    
     fn get_cache_file_path(&self) -> Result<PathBuf> {
         let cache_key = self.get_cache_key()?;
-        Ok(self.cache_dir.join(format!("{}.json", cache_key)))
+        let extension = match self.cache_format {
+            CacheFormat::Json => "json",
+            CacheFormat::Bincode => "bin",
+        };
+        Ok(self.cache_dir.join(format!("{}.{}", cache_key, extension)))
     }
     
     pub fn clear_cache(&self) -> Result<()> {
         if self.cache_dir.exists() {
             fs::remove_dir_all(&self.cache_dir)
-                .map_err(|e| GitMetricsError::IoError(e))?;
+                .map_err(GitMetricsError::IoError)?;
             fs::create_dir_all(&self.cache_dir)
-                .map_err(|e| GitMetricsError::IoError(e))?;
+                .map_err(GitMetricsError::IoError)?;
             info!("Cache cleared successfully.");
         }
         Ok(())
@@ -98,10 +722,10 @@ impl GitCollector {
         }
 
         let metadata = fs::metadata(&cache_file)
-            .map_err(|e| GitMetricsError::IoError(e))?;
+            .map_err(GitMetricsError::IoError)?;
         
         let modified = metadata.modified()
-            .map_err(|e| GitMetricsError::IoError(e))?;
+            .map_err(GitMetricsError::IoError)?;
         
         let now = SystemTime::now();
         let duration = now.duration_since(modified)
@@ -112,10 +736,10 @@ impl GitCollector {
             return Ok(None);
         }
 
-        let data = fs::read_to_string(&cache_file)
-            .map_err(|e| GitMetricsError::IoError(e))?;
+        let data = fs::read(&cache_file)
+            .map_err(GitMetricsError::IoError)?;
         
-        match serde_json::from_str::<Vec<Commit>>(&data) {
+        match decode_commits(&data, self.cache_format) {
             Ok(commits) => {
                 info!("Loading git history from cache ({} commits)...", commits.len());
                 Ok(Some(commits))
@@ -130,388 +754,3824 @@ impl GitCollector {
     fn save_to_cache(&self, commits: &[Commit]) -> Result<()> {
         let cache_file = self.get_cache_file_path()?;
         
-        let json = serde_json::to_string(commits)
-            .map_err(|e| GitMetricsError::SerializationError(e))?;
+        let data = encode_commits(commits, self.cache_format)?;
         
-        fs::write(&cache_file, json)
-            .map_err(|e| GitMetricsError::IoError(e))?;
+        fs::write(&cache_file, data)
+            .map_err(GitMetricsError::IoError)?;
         
         info!("Saved {} commits to cache.", commits.len());
         
         Ok(())
     }
     
+    pub fn last_command(&self) -> Option<String> {
+        self.last_command.lock().ok().and_then(|last_command| last_command.clone())
+    }
+    
     fn run_git_command(&self, args: &[&str]) -> Result<String> {
+        let stdout = self.run_git_command_bytes(args, None)?;
+        Ok(String::from_utf8_lossy(&stdout).to_string())
+    }
+    
+    fn git_command(&self, args: &[&str]) -> Command {
         let mut cmd = Command::new("git");
         cmd.current_dir(&self.repo_path);
         cmd.arg("--no-pager");
+        if let Some(git_dir) = &self.git_dir {
+            cmd.arg(format!("--git-dir={}", git_dir));
+        }
+        if let Some(work_tree) = &self.work_tree {
+            cmd.arg(format!("--work-tree={}", work_tree));
+        }
+        for setting in GIT_CONFIG_OVERRIDES {
+            cmd.args(["-c", setting]);
+        }
         cmd.args(args);
         
-        debug!("Running git command: git --no-pager {}", args.join(" "));
+        // Keep pagers, tracing and prompts from leaking into the output we parse or hanging the run.
+        for var in SCRUBBED_ENV_VARS {
+            cmd.env_remove(var);
+        }
+        cmd.env("GIT_TERMINAL_PROMPT", "0");
+        cmd
+    }
+    
+    fn record_command(&self, args: &[&str]) -> String {
+        let mut command_line = "git --no-pager".to_string();
+        if let Some(git_dir) = &self.git_dir {
+            command_line.push_str(&format!(" --git-dir={}", git_dir));
+        }
+        if let Some(work_tree) = &self.work_tree {
+            command_line.push_str(&format!(" --work-tree={}", work_tree));
+        }
+        command_line.push_str(&format!(" {}", args.join(" ")));
+        debug!("Running git command: {}", command_line);
+        
+        if self.record_commands {
+            if let Ok(mut last_command) = self.last_command.lock() {
+                *last_command = Some(command_line.clone());
+            }
+        }
+        command_line
+    }
+    
+    fn run_git_command_bytes(&self, args: &[&str], input: Option<Vec<u8>>) -> Result<Vec<u8>> {
+        self.check_repository_location()?;
+        let mut cmd = self.git_command(args);
+        cmd.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() });
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+        
+        let command_line = self.record_command(args);
+        
+        let mut child = cmd.spawn()
+            .map_err(|e| GitMetricsError::Other(format!("Failed to execute git command: {}", e)))?;
+        
+        // Feed stdin from a separate thread so a large output can't deadlock us.
+        let writer = match (input, child.stdin.take()) {
+            (Some(input), Some(mut stdin)) => Some(thread::spawn(move || stdin.write_all(&input))),
+            _ => None,
+        };
+        
+        let stderr_reader = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut buffer = Vec::new();
+                let _ = stderr.read_to_end(&mut buffer);
+                buffer
+            })
+        });
+        
+        let mut stdout = Vec::new();
+        if let Some(mut pipe) = child.stdout.take() {
+            let mut chunk = [0u8; 64 * 1024];
+            loop {
+                let read = pipe.read(&mut chunk).map_err(GitMetricsError::IoError)?;
+                if read == 0 {
+                    break;
+                }
+                stdout.extend_from_slice(&chunk[..read]);
+                
+                if let Some(limit) = self.max_output_bytes.filter(|limit| stdout.len() > *limit) {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(GitMetricsError::CommandError(format!(
+                        "Git output exceeded the {} byte limit; narrow the file patterns, commit limit or date range",
+                        limit
+                    )));
+                }
+            }
+        }
         
-        let output = cmd.output()
+        let status = child.wait()
             .map_err(|e| GitMetricsError::Other(format!("Failed to execute git command: {}", e)))?;
+        let stderr = stderr_reader
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+        
+        if let Some(writer) = writer {
+            writer.join()
+                .map_err(|_| GitMetricsError::Other("Failed to write git command input".to_string()))?
+                .map_err(GitMetricsError::IoError)?;
+        }
         
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
+        if !status.success() {
+            let error = String::from_utf8_lossy(&stderr);
+            if self.record_commands {
+                return Err(GitMetricsError::CommandError(
+                    format!("Git command failed ({}): {}", command_line, error)
+                ));
+            }
             return Err(GitMetricsError::CommandError(
                 format!("Git command failed: {}", error)
             ));
         }
         
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
         Ok(stdout)
     }
     
-    pub fn collect_history(&self) -> Result<Vec<Commit>> {
-        if let Some(commits) = self.load_from_cache()? {
-            return Ok(commits);
-        }
-        
-        self.log_collection_start();
+    pub fn list_tracked_files(&self) -> Result<Vec<String>> {
+        let output = self.run_git_command(&["ls-tree", "-r", "-z", "--name-only", "HEAD"])?;
         
-        let commits = self.fetch_commits_batch()?;
-        info!("\nCollected {} commits", commits.len());
-        
-        self.save_to_cache(&commits)?;
-        
-        Ok(commits)
+        Ok(output.split('\0')
+            .filter(|filename| !filename.is_empty() && self.matches_file_pattern(filename))
+            .map(str::to_string)
+            .collect())
     }
     
-    fn log_collection_start(&self) {
-        let pattern_str = if self.file_patterns.is_empty() {
-            "all files".to_string()
-        } else {
-            format!("files matching: {}", self.file_patterns.join(", "))
-        };
-        
-        match (self.max_commits, self.since_days) {
-            (None, None) => {
-                info!("Collecting entire git history ({})...", pattern_str);
-            },
-            (Some(max), Some(days)) => {
-                info!("Collecting git history (limited to {} commits from the last {} days, {})...", 
-                      max, days, pattern_str);
-            },
-            (Some(max), None) => {
-                info!("Collecting git history (limited to {} commits, {})...", 
-                      max, pattern_str);
-            },
-            (None, Some(days)) => {
-                info!("Collecting git history (from the last {} days, {})...", 
-                      days, pattern_str);
-            },
-        };
-    }
-
-    fn fetch_commits_batch(&self) -> Result<Vec<Commit>> {
-        let base_args = [
-            "log",
-            "--pretty=format:COMMIT_START%n%H%n%an%n%ad%n%s%n%b%nCOMMIT_END",
-            "--name-status"
-        ];
+    pub fn line_counts(&self) -> Result<Vec<FileLineCount>> {
+        let files = self.list_tracked_files()?;
+        info!("Counting lines in {} tracked files...", files.len());
         
-        let owned_strings = self.build_commit_args();
-        let mut all_args = Vec::with_capacity(base_args.len() + owned_strings.len());
-        all_args.extend_from_slice(&base_args);
+        let input: String = files.iter()
+            .map(|filename| format!("HEAD:{}\n", filename))
+            .collect();
+        let output = self.run_git_command_bytes(&["cat-file", "--batch"], Some(input.into_bytes()))?;
         
-        for owned in &owned_strings {
-            all_args.push(owned.as_str());
+        let blobs = split_batch_output(&output)?;
+        if blobs.len() != files.len() {
+            return Err(GitMetricsError::Other(format!(
+                "Expected {} blobs from git cat-file, got {}", files.len(), blobs.len()
+            )));
         }
         
-        let output = self.run_git_command(&all_args)?;
-        self.parse_commit_data(&output)
+        Ok(files.into_par_iter()
+            .zip(blobs.into_par_iter())
+            .map(|(filename, content)| {
+                let binary = content.iter().take(BINARY_SNIFF_BYTES).any(|&byte| byte == 0);
+                let lines = if binary {
+                    0
+                } else {
+                    let newlines = content.iter().filter(|&&byte| byte == b'\n').count();
+                    newlines + usize::from(content.last().is_some_and(|&byte| byte != b'\n'))
+                };
+                FileLineCount { filename, lines, binary }
+            })
+            .collect())
     }
     
-    fn build_commit_args(&self) -> Vec<String> {
-        let mut args = Vec::new();
-        
-        if let Some(days) = self.since_days {
-            let since_date = Utc::now()
-                .checked_sub_signed(chrono::Duration::days(days as i64))
-                .unwrap_or_else(|| Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap());
-            
-            args.push(format!("--since={}", since_date.format("%Y-%m-%d")));
-        }
+    pub fn current_branch(&self) -> Result<Option<String>> {
+        let output = self.run_git_command(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+        let branch = output.trim();
         
-        if let Some(max) = self.max_commits {
-            args.push(format!("-n {}", max));
+        if branch == "HEAD" {
+            debug!("HEAD is detached");
+            return Ok(None);
         }
         
-        args
+        Ok(Some(branch.to_string()))
     }
     
-    fn parse_commit_data(&self, data: &str) -> Result<Vec<Commit>> {
-        let raw_commits: Vec<&str> = data.split(COMMIT_START_MARKER).skip(1).collect();
-        let total_commits = raw_commits.len();
-        info!("Found {} commits, processing in parallel...", total_commits);
-        
-        let has_file_filters = !self.file_patterns.is_empty();
+    pub fn head_sha(&self) -> Result<String> {
+        let output = self.run_git_command(&["rev-parse", "HEAD"])?;
+        Ok(output.trim().to_string())
+    }
+    
+    pub fn submodule_path(&self, name: &str) -> Result<PathBuf> {
+        let entries = match self.run_git_command(&[
+            "config", "--file", ".gitmodules", "--get-regexp", r"^submodule\..*\.path$"
+        ]) {
+            Ok(output) => output,
+            Err(GitMetricsError::CommandError(_)) => String::new(),
+            Err(e) => return Err(e),
+        };
         
-        let commits: Vec<Commit> = raw_commits.par_iter()
-            .filter_map(|commit_data| {
-                self.parse_single_commit(commit_data).ok()
+        let path = entries.lines()
+            .filter_map(|line| line.split_once(' '))
+            .find_map(|(key, path)| {
+                let submodule_name = key.strip_prefix("submodule.")?.strip_suffix(".path")?;
+                (submodule_name == name || path == name).then(|| path.to_string())
             })
-            .filter(|commit| {
-                !has_file_filters || !commit.files.is_empty()
-            })
-            .collect();
+            .ok_or_else(|| GitMetricsError::Other(format!("Unknown submodule: {}", name)))?;
         
-        Ok(commits)
+        let status = self.run_git_command(&["submodule", "status", "--", &path])?;
+        if status.is_empty() || status.starts_with('-') {
+            return Err(GitMetricsError::Other(format!(
+                "Submodule '{}' is not initialized; run 'git submodule update --init {}' first", name, path
+            )));
+        }
+        
+        Ok(Path::new(&self.repo_path).join(path))
     }
     
-    fn matches_file_pattern(&self, filename: &str) -> bool {
-        if self.file_patterns.is_empty() {
+    pub fn resolve_ref(&self, reference: &str) -> Result<String> {
+        let commit_rev = format!("{}^{{commit}}", reference);
+        match self.run_git_command(&["rev-parse", "--verify", &commit_rev]) {
+            Ok(output) => Ok(output.trim().to_string()),
+            Err(GitMetricsError::CommandError(e)) if e.contains("ambiguous") => {
+                Err(GitMetricsError::Other(format!("Ambiguous ref '{}': {}", reference, e.trim())))
+            },
+            Err(GitMetricsError::CommandError(_)) => {
+                Err(GitMetricsError::Other(format!("Unknown ref: {}", reference)))
+            },
+            Err(e) => Err(e),
+        }
+    }
+    
+    /// Earliest author timestamp for `author_email` over all of the collected revision's history,
+    /// ignoring `max_commits` and `since_days` so a capped collection can't hide the first commit.
+    pub fn author_first_timestamp(&self, author_email: &str) -> Result<Option<i64>> {
+        let (_, email) = self.author_placeholders();
+        let mut args = vec!["log".to_string(), format!("--format={}%x00%at", email)];
+        args.extend(self.revision_args()?);
+        args.push("--".to_string());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self.run_git_command(&args)?;
+        
+        Ok(output.lines()
+            .filter_map(|line| line.split_once('\0'))
+            .filter(|(email, _)| {
+                self.author_aliases.get(*email).map_or(*email, String::as_str).eq_ignore_ascii_case(author_email)
+            })
+            .filter_map(|(_, timestamp)| timestamp.trim().parse::<i64>().ok())
+            .min())
+    }
+    
+    /// Newest entry first, like `git reflog`. The oldest entry shown has no `old_sha` because
+    /// the reflog may have been expired past it.
+    pub fn reflog(&self, reference: &str) -> Result<Vec<ReflogEntry>> {
+        let output = match self.run_git_command(&["reflog", "show", "--date=iso", "--format=%H%x00%gd%x00%gs", reference, "--"]) {
+            Ok(output) => output,
+            Err(GitMetricsError::CommandError(e)) => {
+                if self.run_git_command(&["rev-parse", "--verify", "--quiet", reference]).is_ok() {
+                    return Err(GitMetricsError::CommandError(e));
+                }
+                // An unborn HEAD has nothing to show yet.
+                if reference == "HEAD" {
+                    return Ok(Vec::new());
+                }
+                return Err(GitMetricsError::Other(format!("Unknown ref: {}", reference)));
+            },
+            Err(e) => return Err(e),
+        };
+        
+        let mut entries: Vec<ReflogEntry> = output.lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\0');
+                let new_sha = fields.next()?;
+                let (ref_name, date) = fields.next()?.strip_suffix('}')?.split_once("@{")?;
+                let subject = fields.next().unwrap_or_default();
+                let (action, message) = subject.split_once(": ").unwrap_or((subject, ""));
+                Some(ReflogEntry {
+                    ref_name: ref_name.to_string(),
+                    old_sha: None,
+                    new_sha: new_sha.to_string(),
+                    action: action.to_string(),
+                    message: message.to_string(),
+                    date: date.to_string(),
+                })
+            })
+            .collect();
+        
+        for i in 1..entries.len() {
+            entries[i - 1].old_sha = Some(entries[i].new_sha.clone());
+        }
+        Ok(entries)
+    }
+    
+    pub fn merge_base(&self, ref_a: &str, ref_b: &str) -> Result<String> {
+        match self.run_git_command(&["merge-base", ref_a, ref_b]) {
+            Ok(output) if !output.trim().is_empty() => Ok(output.trim().to_string()),
+            Ok(_) => Err(GitMetricsError::Other(format!(
+                "'{}' and '{}' have no common ancestor", ref_a, ref_b
+            ))),
+            Err(e) => {
+                // merge-base exits 1 without output when the refs are unrelated.
+                let both_resolve = [ref_a, ref_b].iter().all(|rev| {
+                    let commit_rev = format!("{}^{{commit}}", rev);
+                    self.run_git_command(&["rev-parse", "--verify", "--quiet", &commit_rev]).is_ok()
+                });
+                if both_resolve {
+                    Err(GitMetricsError::Other(format!("'{}' and '{}' have no common ancestor", ref_a, ref_b)))
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+    
+    /// `branch_files` are the files changed on `branch` since it forked from `base`, ignoring
+    /// whatever `base` did in the meantime.
+    pub fn branch_divergence(&self, branch: &str, base: &str) -> Result<BranchDivergence> {
+        let range = format!("{}...{}", base, branch);
+        let output = self.run_git_command(&["rev-list", "--left-right", "--count", &range, "--"])?;
+        
+        let counts: Vec<usize> = output.split_whitespace()
+            .filter_map(|count| count.parse().ok())
+            .collect();
+        let [behind, ahead] = counts[..] else {
+            return Err(GitMetricsError::Other(format!("Unexpected rev-list output: {}", output.trim())));
+        };
+        
+        let fork_point = self.merge_base(base, branch)?;
+        Ok(BranchDivergence {
+            ahead,
+            behind,
+            branch_files: self.diff_refs(&fork_point, branch)?,
+        })
+    }
+    
+    /// One entry per pair of consecutive tags, ordered by tag date or, with `by_version`, by
+    /// version-aware tag name. Fewer than two tags give an empty list.
+    pub fn tag_ranges(&self, by_version: bool) -> Result<Vec<TagRange>> {
+        let sort = if by_version { "--sort=version:refname" } else { "--sort=creatordate" };
+        let output = self.run_git_command(&["for-each-ref", sort, "--format=%(refname:short)", "refs/tags"])?;
+        let tags: Vec<&str> = output.lines().filter(|tag| !tag.is_empty()).collect();
+        
+        tags.windows(2)
+            .map(|pair| {
+                let (from, to) = (pair[0], pair[1]);
+                let range = format!("{}..{}", from, to);
+                let commit_count = self.run_git_command(&["rev-list", "--count", &range, "--"])?
+                    .trim()
+                    .parse()
+                    .map_err(|e| GitMetricsError::Other(format!("Unexpected rev-list output: {}", e)))?;
+                let summary = parse_shortstat(&self.run_git_command(&["diff", "--shortstat", from, to, "--"])?);
+                
+                Ok(TagRange {
+                    from_tag: from.to_string(),
+                    to_tag: to.to_string(),
+                    commit_count,
+                    files_changed: summary.files_changed,
+                    insertions: summary.insertions,
+                    deletions: summary.deletions,
+                })
+            })
+            .collect()
+    }
+    
+    fn diff_refs(&self, from: &str, to: &str) -> Result<Vec<String>> {
+        let output = self.run_git_command(&["diff", "--name-only", "--no-renames", from, to, "--"])?;
+        Ok(output.lines().map(str::to_string).collect())
+    }
+    
+    pub fn git_version(&self) -> Result<Option<(u32, u32, u32)>> {
+        if let Some(version) = self.git_version.get() {
+            return Ok(*version);
+        }
+        
+        let output = self.run_git_command(&["--version"])?;
+        let version = parse_git_version(&output);
+        if version.is_none() {
+            warn!("Could not parse git version from '{}'", output.trim());
+        }
+        Ok(*self.git_version.get_or_init(|| version))
+    }
+    
+    fn check_git_version(&self) -> Result<()> {
+        if self.fast_path {
+            return Ok(());
+        }
+        let Some(version) = self.git_version()? else {
+            return Ok(());
+        };
+        
+        let required = if self.date_format() == "unix" {
+            (UNIX_DATE_GIT_VERSION, "--date=unix")
+        } else {
+            (MIN_GIT_VERSION, "--date=iso-strict and %aI")
+        };
+        
+        if version < required.0 {
+            return Err(GitMetricsError::Other(format!(
+                "git {}.{}.{} is too old; {} need git {}.{} or newer",
+                version.0, version.1, version.2, required.1, (required.0).0, (required.0).1
+            )));
+        }
+        Ok(())
+    }
+    
+    pub fn is_shallow(&self) -> Result<bool> {
+        match self.run_git_command(&["rev-parse", "--is-shallow-repository"]) {
+            Ok(output) if matches!(output.trim(), "true" | "false") => Ok(output.trim() == "true"),
+            _ => {
+                // Older git versions don't know the flag and echo it back instead.
+                let git_dir = self.run_git_command(&["rev-parse", "--git-dir"])?;
+                let git_dir = Path::new(&self.repo_path).join(git_dir.trim());
+                Ok(git_dir.join("shallow").exists())
+            }
+        }
+    }
+
+    fn check_shallow(&self) -> Result<()> {
+        if self.fast_path || !self.is_shallow()? {
+            return Ok(());
+        }
+
+        let message = "Repository is a shallow clone; history is truncated and metrics may be misleading";
+        if self.fail_on_shallow {
+            return Err(GitMetricsError::Other(format!(
+                "{} (fetch full history with `git fetch --unshallow`)", message
+            )));
+        }
+
+        warn!("{}", message);
+        Ok(())
+    }
+
+    fn check_clean(&self) -> Result<()> {
+        let status = self.run_git_command(&["status", "--porcelain"])?;
+        let dirty: Vec<&str> = status.lines()
+            .filter_map(|line| line.get(3..))
+            .collect();
+        
+        if dirty.is_empty() {
+            return Ok(());
+        }
+        Err(GitMetricsError::Other(format!(
+            "Working tree has uncommitted changes: {}", dirty.join(", ")
+        )))
+    }
+
+    pub fn collect_history(&self) -> Result<Vec<Commit>> {
+        self.validate_exclude_authors()?;
+        if self.require_clean {
+            self.check_clean()?;
+        }
+        
+        let (commits, _) = self.load_or_fetch()?;
+        let commits = self.apply_post_filters(commits);
+        self.check_pattern_hits(&commits);
+        Ok(commits)
+    }
+    
+    pub fn warm_cache(&self) -> Result<CacheStatus> {
+        let (commits, rebuilt) = self.load_or_fetch()?;
+        self.check_pattern_hits(&commits);
+        Ok(CacheStatus { commit_count: commits.len(), rebuilt })
+    }
+    
+    fn load_or_fetch(&self) -> Result<(Vec<Commit>, bool)> {
+        self.check_shallow()?;
+
+        match self.load_from_cache()? {
+            Some(commits) => Ok((commits, false)),
+            None => {
+                self.log_collection_start();
+                
+                let commits = self.fetch_commits_batch()?;
+                info!("\nCollected {} commits", commits.len());
+                
+                self.save_to_cache(&commits)?;
+                Ok((commits, true))
+            }
+        }
+    }
+    
+    pub fn export_history(&self, path: &str, format: CacheFormat) -> Result<usize> {
+        Self::write_export(path, &self.collect_history()?, format)
+    }
+    
+    pub fn write_export(path: &str, commits: &[Commit], format: CacheFormat) -> Result<usize> {
+        fs::write(path, encode_commits(commits, format)?)
+            .map_err(GitMetricsError::IoError)?;
+        Ok(commits.len())
+    }
+    
+    pub fn read_export(path: &str, format: CacheFormat) -> Result<Vec<Commit>> {
+        let data = fs::read(path)
+            .map_err(GitMetricsError::IoError)?;
+        decode_commits(&data, format)
+    }
+    
+    fn apply_post_filters(&self, commits: Vec<Commit>) -> Vec<Commit> {
+        let commits = self.remove_excluded_authors(commits);
+        
+        match self.max_commits_per_author {
+            Some(limit) => analysis::latest_per_author(commits, limit),
+            None => commits,
+        }
+    }
+    
+    fn remove_excluded_authors(&self, commits: Vec<Commit>) -> Vec<Commit> {
+        if self.exclude_authors.is_empty() {
+            return commits;
+        }
+        
+        let total = commits.len();
+        let kept: Vec<Commit> = commits.into_iter()
+            .filter(|commit| !self.is_excluded_author(commit))
+            .collect();
+        
+        debug!("Excluded {} commits by author", total - kept.len());
+        kept
+    }
+    
+    fn is_excluded_author(&self, commit: &Commit) -> bool {
+        // Callers validate the patterns up front, so an invalid regex never reaches here.
+        let Ok(matchers) = self.exclude_author_matchers() else {
+            return false;
+        };
+        
+        matchers.iter().any(|(entry, regex)| {
+            [&commit.author, &commit.author_email].iter().any(|field| match regex {
+                Some(regex) => regex.is_match(field),
+                None => field.contains(entry.as_str()),
+            })
+        })
+    }
+    
+    fn log_collection_start(&self) {
+        let pattern_str = if self.file_patterns.is_empty() {
+            "all files".to_string()
+        } else {
+            format!("files matching: {}", self.file_patterns.join(", "))
+        };
+        
+        match (self.max_commits, self.since_days) {
+            (None, None) => {
+                info!("Collecting entire git history ({})...", pattern_str);
+            },
+            (Some(max), Some(days)) => {
+                info!("Collecting git history (limited to {} commits from the last {} days, {})...", 
+                      max, days, pattern_str);
+            },
+            (Some(max), None) => {
+                info!("Collecting git history (limited to {} commits, {})...", 
+                      max, pattern_str);
+            },
+            (None, Some(days)) => {
+                info!("Collecting git history (from the last {} days, {})...", 
+                      days, pattern_str);
+            },
+        };
+    }
+
+    fn fetch_commits_batch(&self) -> Result<Vec<Commit>> {
+        self.validate_extra_log_args()?;
+        self.validate_markers()?;
+        self.check_git_version()?;
+        
+        let mut all_args = self.log_args();
+        all_args.extend(self.revision_args()?);
+        all_args.extend(self.pathspec_args()?);
+        
+        let all_args: Vec<&str> = all_args.iter().map(String::as_str).collect();
+        let output = self.decode_log_output(&self.run_git_command_bytes(&all_args, None)?);
+        let mut commits = self.parse_commit_data(&output)?;
+        
+        if self.detect_whitespace_only {
+            let substantive = self.substantive_files()?;
+            for commit in &mut commits {
+                // Only files that survived the Rust-side filters count, so a filtered-out file
+                // with real changes can't hide a whitespace-only commit.
+                let kept: HashSet<&str> = commit.files.iter().map(|file| file.filename.as_str()).collect();
+                let has_substantive_file = substantive.get(&commit.hash).is_some_and(|paths| {
+                    paths.iter().any(|path| kept.contains(self.strip_prefix(path).as_str()))
+                });
+                commit.is_whitespace_only = commit.has_line_changes && !has_substantive_file;
+            }
+        }
+        
+        Ok(commits)
+    }
+    
+    // Messages git could not re-encode arrive in their declared encoding; see RecordDecoder.
+    fn decode_log_output(&self, output: &[u8]) -> String {
+        if !self.capture_encoding {
+            return String::from_utf8_lossy(output).into_owned();
+        }
+        
+        let mut decoder = self.record_decoder();
+        output.split_inclusive(|&byte| byte == b'\n')
+            .map(|line| decoder.decode_line(line))
+            .collect()
+    }
+    
+    // Per hash, the files whose diff still has changes with whitespace ignored; under `-w` git
+    // drops whitespace-only files from numstat entirely.
+    fn substantive_files(&self) -> Result<HashMap<String, Vec<String>>> {
+        let mut args = vec!["log".to_string(), "--format=%x00%H".to_string(), "-w".to_string(), "--numstat".to_string()];
+        if self.include_merge_diffs {
+            args.push("--cc".to_string());
+        }
+        args.extend(self.build_commit_args());
+        args.extend(self.revision_args()?);
+        args.extend(self.pathspec_args()?);
+        
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self.run_git_command(&args)?;
+        
+        let mut substantive: HashMap<String, Vec<String>> = HashMap::new();
+        let mut current = None;
+        for line in output.lines() {
+            if let Some(hash) = line.strip_prefix('\0') {
+                current = Some(hash);
+            } else if let (Some(hash), Some((counts, path))) = (current, line.rsplit_once('\t')) {
+                // Binary files report "-\t-", which is never whitespace-only.
+                if counts != "0\t0" {
+                    substantive.entry(hash.to_string()).or_default().push(numstat_new_path(path));
+                }
+            }
+        }
+        Ok(substantive)
+    }
+    
+    fn unmatched_patterns(&self) -> Vec<&str> {
+        self.file_patterns.iter()
+            .zip(&self.pattern_hits)
+            .filter(|(_, hit)| !hit.load(Ordering::Relaxed))
+            .map(|(pattern, _)| pattern.as_str())
+            .collect()
+    }
+    
+    // Hits are worked out from the commits handed back, so cache hits and post-filters count the
+    // same as a fresh parse. File names are matched before `strip_path_prefix` applies.
+    fn record_pattern_hits<'a>(&self, commits: impl IntoIterator<Item = &'a Commit>) {
+        let prefix = self.strip_path_prefix.as_deref().map(|prefix| prefix.trim_end_matches('/'));
+        for file in commits.into_iter().flat_map(|commit| &commit.files) {
+            let prefixed = prefix.map(|prefix| format!("{}/{}", prefix, file.filename));
+            for (pattern, hit) in self.file_patterns.iter().zip(&self.pattern_hits) {
+                let matches = |name: &str| Self::pattern_matches(pattern, name);
+                if !hit.load(Ordering::Relaxed) && (matches(&file.filename) || prefixed.as_deref().is_some_and(matches)) {
+                    hit.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+    
+    fn reset_pattern_hits(&self) {
+        for hit in &self.pattern_hits {
+            hit.store(false, Ordering::Relaxed);
+        }
+    }
+    
+    fn check_pattern_hits(&self, commits: &[Commit]) {
+        self.reset_pattern_hits();
+        self.record_pattern_hits(commits);
+        self.warn_unmatched_patterns();
+    }
+    
+    fn warn_unmatched_patterns(&self) {
+        for pattern in self.unmatched_patterns() {
+            warn!("File pattern '{}' did not match any file in the collected history", pattern);
+        }
+    }
+    
+    fn log_args(&self) -> Vec<String> {
+        let mut args = vec!["log".to_string()];
+        args.extend(self.record_args());
+        args.extend(self.build_commit_args());
+        args
+    }
+    
+    // What every record needs for `parse_record`, whether it comes from a walk or a lookup.
+    fn record_args(&self) -> Vec<String> {
+        let mut args = vec![self.log_format(), "--raw".to_string(), "--numstat".to_string()];
+        if self.include_merge_diffs {
+            args.push("--cc".to_string());
+        }
+        args
+    }
+    
+    pub fn plan(&self) -> Result<Vec<String>> {
+        self.validate_extra_log_args()?;
+        self.validate_markers()?;
+        self.validate_exclude_authors()?;
+        
+        self.validate_date_range()?;
+        
+        let mut args = self.log_args();
+        if self.since_last_tag {
+            // Resolving the tag would run git, so the range stays symbolic.
+            args.push(format!("<last-tag>..{}", self.tip()?));
+        } else if self.ref_name.is_some() {
+            args.push(self.tip()?.to_string());
+        }
+        // Likewise following renames would run git, so the path's former names stay symbolic.
+        args.extend(self.build_pathspec_args(|path| Ok(vec![format!("<names-of:{}>", path)]))?);
+        Ok(args)
+    }
+    
+    pub fn stream_commits(self) -> Result<CommitStream> {
+        if self.max_commits_per_author.is_some() {
+            return Err(GitMetricsError::Other("max_commits_per_author is not supported when streaming".to_string()));
+        }
+        self.validate_extra_log_args()?;
+        self.validate_markers()?;
+        self.validate_exclude_authors()?;
+        self.check_git_version()?;
+        self.check_shallow()?;
+        self.log_collection_start();
+        self.reset_pattern_hits();
+        
+        let mut args = self.log_args();
+        args.extend(self.revision_args()?);
+        args.extend(self.pathspec_args()?);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        
+        let mut cmd = self.git_command(&args);
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+        self.record_command(&args);
+        
+        let mut child = cmd.spawn()
+            .map_err(|e| GitMetricsError::Other(format!("Failed to execute git command: {}", e)))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| GitMetricsError::Other("Failed to capture git output".to_string()))?;
+        let stderr_reader = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut buffer = Vec::new();
+                let _ = stderr.read_to_end(&mut buffer);
+                buffer
+            })
+        });
+        
+        Ok(CommitStream {
+            decoder: self.record_decoder(),
+            collector: self,
+            child,
+            reader: BufReader::new(stdout),
+            stderr_reader,
+            record: String::new(),
+            records_read: 0,
+            yielded: 0,
+            finished: false,
+        })
+    }
+    
+    pub fn raw_log(&self) -> Result<Vec<u8>> {
+        self.validate_extra_log_args()?;
+        self.check_git_version()?;
+        
+        let log_format = self.raw_log_format();
+        let mut args = vec!["log".to_string(), log_format, "-z".to_string(), "--raw".to_string(), "--numstat".to_string()];
+        if self.include_merge_diffs {
+            args.push("--cc".to_string());
+        }
+        args.extend(self.build_commit_args());
+        args.extend(self.revision_args()?);
+        args.extend(self.pathspec_args()?);
+        
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.run_git_command_bytes(&args, None)
+    }
+    
+    pub fn last_tag(&self) -> Result<Option<String>> {
+        match self.run_git_command(&["describe", "--tags", "--abbrev=0", self.tip()?]) {
+            Ok(output) => Ok(Some(output.trim().to_string())),
+            Err(GitMetricsError::CommandError(e)) => {
+                debug!("No tag found: {}", e);
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+    
+    // Where the walk starts: `ref_name` or HEAD. Refs are positional, so reject option lookalikes.
+    fn tip(&self) -> Result<&str> {
+        match self.ref_name.as_deref() {
+            Some(ref_name) if ref_name.is_empty() || ref_name.starts_with('-') => {
+                Err(GitMetricsError::Other(format!("Invalid ref_name '{}'", ref_name)))
+            },
+            Some(ref_name) => Ok(ref_name),
+            None => Ok("HEAD"),
+        }
+    }
+    
+    // The revision is the one positional argument; dates only narrow it (see `build_commit_args`).
+    fn revision_args(&self) -> Result<Vec<String>> {
+        self.validate_date_range()?;
+        // Resolved to a hash so a ref that is also a file name can't be read as a path.
+        let tip = match &self.ref_name {
+            Some(_) => Some(self.resolve_ref(self.tip()?)?),
+            None => None,
+        };
+        
+        if self.since_last_tag {
+            match self.last_tag()? {
+                Some(tag) => return Ok(vec![format!("{}..{}", tag, tip.as_deref().unwrap_or("HEAD"))]),
+                None => warn!("No tags found; collecting the full history instead of commits since the last tag"),
+            }
+        }
+        Ok(tip.into_iter().collect())
+    }
+    
+    fn validate_date_range(&self) -> Result<()> {
+        if self.since_days.is_some() && self.since_date.is_some() {
+            return Err(GitMetricsError::Other("since_days and since_date are mutually exclusive".to_string()));
+        }
+        for (option, date) in [("since_date", &self.since_date), ("until_date", &self.until_date)] {
+            if date.as_deref().is_some_and(|date| date.trim().is_empty() || date.contains(['\n', '\0'])) {
+                return Err(GitMetricsError::Other(format!("Invalid {}", option)));
+            }
+        }
+        Ok(())
+    }
+    
+    // Must come last in the argument list: everything after `--` is a path.
+    fn pathspec_args(&self) -> Result<Vec<String>> {
+        self.build_pathspec_args(|path| {
+            Ok(self.path_names(path)?.iter().map(|name| format!(":(literal){}", name)).collect())
+        })
+    }
+    
+    fn build_pathspec_args(&self, follow_names: impl FnOnce(&str) -> Result<Vec<String>>) -> Result<Vec<String>> {
+        if let Some(path) = &self.follow_path {
+            if !self.pathspecs.is_empty() {
+                return Err(GitMetricsError::Other("follow_path cannot be combined with pathspecs".to_string()));
+            }
+            let mut args = vec!["--".to_string()];
+            args.extend(follow_names(path)?);
+            return Ok(args);
+        }
+        if self.pathspecs.is_empty() && self.extensions.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        for pathspec in &self.pathspecs {
+            let unclosed_magic = pathspec.starts_with(":(") && !pathspec.contains(')');
+            if pathspec.is_empty() || pathspec.contains(['\n', '\0']) || unclosed_magic {
+                return Err(GitMetricsError::Other(format!("Invalid pathspec '{}'", pathspec.escape_debug())));
+            }
+        }
+        for extension in &self.extensions {
+            if extension.is_empty() || extension.contains(['/', '*', '?', '[', '\n', '\0']) {
+                return Err(GitMetricsError::Other(format!("Invalid extension '{}'", extension.escape_debug())));
+            }
+        }
+        
+        // Positive pathspecs are OR-ed by git, so a path outside the extensions can still reach
+        // parse_file_changes; matches_extension keeps the two combined as an AND.
+        let mut args = vec!["--".to_string()];
+        args.extend(self.pathspecs.iter().cloned());
+        args.extend(self.extensions.iter().map(|extension| format!("*.{}", extension)));
+        Ok(args)
+    }
+    
+    /// Every name `path` has had in the history of `ref_name` (or HEAD), following renames.
+    pub fn path_names(&self, path: &str) -> Result<BTreeSet<String>> {
+        let output = self.run_git_command(&["log", "--follow", "-M", "--name-status", "--format=", self.tip()?, "--", path])?;
+        
+        let names: BTreeSet<String> = output.lines()
+            .filter_map(|line| line.split_once('\t'))
+            .flat_map(|(_, names)| names.split('\t'))
+            .map(str::to_string)
+            .collect();
+        if names.is_empty() {
+            return Err(GitMetricsError::Other(format!("No history for path '{}'", path)));
+        }
+        Ok(names)
+    }
+    
+    pub fn get_commit(&self, hash: &str) -> Result<Commit> {
+        self.validate_markers()?;
+        let commit_rev = format!("{}^{{commit}}", hash);
+        if self.run_git_command(&["rev-parse", "--verify", "--quiet", &commit_rev]).is_err() {
+            return Err(GitMetricsError::Other(format!("Unknown commit: {}", hash)));
+        }
+        
+        let mut args = vec!["log".to_string(), "--no-walk".to_string(), "-1".to_string()];
+        args.extend(self.record_args());
+        args.extend(self.display_args());
+        args.push(commit_rev);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        
+        let output = self.decode_log_output(&self.run_git_command_bytes(&args, None)?);
+        let commit_data = output.split(self.record_separator().as_str())
+            .nth(1)
+            .ok_or_else(|| GitMetricsError::Other(format!("No commit data returned for {}", hash)))?;
+        
+        self.parse_record(commit_data)
+    }
+    
+    pub fn get_commits(&self, hashes: &[String]) -> Result<Vec<Commit>> {
+        self.validate_markers()?;
+        let lookup: String = hashes.iter()
+            .map(|hash| format!("{}^{{commit}}\n", hash.trim()))
+            .collect();
+        let lookup = self.run_git_command_bytes(
+            &["cat-file", "--batch-check=%(objectname) %(objecttype)"],
+            Some(lookup.into_bytes())
+        )?;
+        
+        let mut known = String::new();
+        for (hash, line) in hashes.iter().zip(String::from_utf8_lossy(&lookup).lines()) {
+            match line.split_once(' ') {
+                Some((sha, "commit")) => {
+                    known.push_str(sha);
+                    known.push('\n');
+                },
+                _ => warn!("Skipping unknown commit {}", hash),
+            }
+        }
+        
+        if known.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let mut args = vec!["log".to_string(), "--no-walk=unsorted".to_string(), "--stdin".to_string()];
+        args.extend(self.record_args());
+        args.extend(self.display_args());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        
+        let output = self.decode_log_output(&self.run_git_command_bytes(&args, Some(known.into_bytes()))?);
+        
+        Ok(output.split(self.record_separator().as_str())
+            .skip(1)
+            .filter_map(|commit_data| match self.parse_record(commit_data) {
+                Ok(commit) => Some(commit),
+                Err(e) => {
+                    warn!("Skipping commit record: {}", e);
+                    None
+                }
+            })
+            .enumerate()
+            .map(|(index, commit)| Commit { index, ..commit })
+            .collect())
+    }
+    
+    fn validate_extra_log_args(&self) -> Result<()> {
+        for arg in &self.extra_log_args {
+            let flag = arg.split('=').next().unwrap_or(arg);
+            if RESERVED_LOG_ARGS.contains(&flag) {
+                return Err(GitMetricsError::Other(format!(
+                    "Extra git log argument '{}' would change the output format and is not allowed", arg
+                )));
+            }
+        }
+        Ok(())
+    }
+    
+    fn build_commit_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        
+        if let Some(days) = self.since_days {
+            let since_date = Utc::now()
+                .checked_sub_signed(chrono::Duration::days(days as i64))
+                .unwrap_or_else(|| Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap());
+            
+            args.push(format!("--since={}", since_date.format("%Y-%m-%d")));
+        }
+        
+        if let Some(date) = &self.since_date {
+            args.push(format!("--since={}", date));
+        }
+        
+        if let Some(date) = &self.until_date {
+            args.push(format!("--until={}", date));
+        }
+        
+        if let Some(author_email) = &self.author_email {
+            // git matches `--author` against the mailmapped identity only when mailmap is on.
+            args.push(if self.mailmap_enabled() { "--use-mailmap" } else { "--no-use-mailmap" }.to_string());
+            let aliases = self.author_aliases.iter()
+                .filter(|(_, canonical)| canonical.eq_ignore_ascii_case(author_email))
+                .map(|(alias, _)| alias);
+            for email in std::iter::once(author_email).chain(aliases) {
+                args.push(format!("--author=<{}>", case_insensitive_pattern(email)));
+            }
+        }
+        
+        if let Some(max) = self.max_commits {
+            args.push(format!("-n {}", max));
+        }
+        
+        if self.ascending {
+            args.push("--reverse".to_string());
+        }
+        
+        if let Some(order) = self.traversal_order {
+            args.push(match order {
+                TraversalOrder::Date => "--date-order",
+                TraversalOrder::AuthorDate => "--author-date-order",
+                TraversalOrder::Topo => "--topo-order",
+            }.to_string());
+        }
+        
+        args.extend(self.display_args());
+        args.extend(self.extra_log_args.iter().cloned());
+        
+        args
+    }
+    
+    fn display_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        
+        if let Some(length) = self.abbrev_length {
+            args.push(format!("--abbrev={}", length));
+        }
+        
+        args.push(format!("--date={}", self.date_format()));
+        if self.capture_encoding {
+            args.push("--encoding=UTF-8".to_string());
+        }
+        args.extend(self.rename_args());
+        args
+    }
+    
+    fn rename_args(&self) -> Vec<String> {
+        let threshold = self.rename_threshold
+            .map_or_else(String::new, |percent| format!("{}%", percent.min(100)));
+        
+        let mut args = Vec::new();
+        match self.detect_renames {
+            Some(false) if !self.detect_copies => args.push("--no-renames".to_string()),
+            Some(true) => args.push(format!("-M{}", threshold)),
+            None if self.rename_threshold.is_some() && !self.detect_copies => args.push(format!("-M{}", threshold)),
+            _ => {},
+        }
+        if self.detect_copies {
+            args.push(format!("-C{}", threshold));
+        }
+        args
+    }
+    
+    fn parse_commit_data(&self, data: &str) -> Result<Vec<Commit>> {
+        let separator = self.record_separator();
+        let raw_commits: Vec<&str> = data.split(separator.as_str()).skip(1).collect();
+        let total_commits = raw_commits.len();
+        info!("Found {} commits, processing in parallel...", total_commits);
+        
+        let parsed: Vec<Result<Commit>> = raw_commits.par_iter()
+            .enumerate()
+            .map(|(index, commit_data)| {
+                self.parse_record(commit_data)
+                    .map(|commit| Commit { index, ..commit })
+            })
+            .collect();
+        
+        let mut commits = Vec::with_capacity(parsed.len());
+        let mut skipped = 0;
+        
+        for result in parsed {
+            match result {
+                Ok(commit) => {
+                    if self.keeps_parsed_commit(&commit) {
+                        commits.push(commit);
+                    }
+                },
+                Err(e) => {
+                    skipped += 1;
+                    debug!("Skipping commit record: {}", e);
+                }
+            }
+        }
+        
+        if skipped > 0 {
+            warn!("Skipped {} of {} commit records that could not be parsed", skipped, total_commits);
+        }
+        
+        Ok(commits)
+    }
+    
+    fn keeps_parsed_commit(&self, commit: &Commit) -> bool {
+        let filters_files = !self.file_patterns.is_empty()
+            || !self.extensions.is_empty()
+            || !self.exclude_paths.is_empty()
+            || self.status_filter.is_some();
+        let drop_empty = !self.include_empty && filters_files;
+        let below_min_files = self.min_files.is_some_and(|min| commit.files.len() < min);
+        (!drop_empty || !commit.files.is_empty()) && !below_min_files
+    }
+    
+    pub fn is_code_file(&self, filename: &str) -> bool {
+        let Some(extension) = Path::new(filename).extension().and_then(|ext| ext.to_str()) else {
+            return false;
+        };
+        let extension = extension.to_ascii_lowercase();
+        
+        match &self.code_extensions {
+            Some(extensions) => extensions.iter()
+                .any(|code_extension| code_extension.trim_start_matches('.').eq_ignore_ascii_case(&extension)),
+            None => DEFAULT_CODE_EXTENSIONS.contains(&extension.as_str()),
+        }
+    }
+    
+    fn strip_prefix(&self, filename: &str) -> String {
+        let Some(prefix) = self.strip_path_prefix.as_deref() else {
+            return filename.to_string();
+        };
+        
+        filename.strip_prefix(prefix.trim_end_matches('/'))
+            .and_then(|rest| rest.strip_prefix('/'))
+            .unwrap_or(filename)
+            .to_string()
+    }
+    
+    fn matches_status_filter(&self, status: &str) -> bool {
+        match (&self.status_filter, status.chars().next()) {
+            (None, _) => true,
+            (Some(statuses), Some(letter)) => statuses.contains(&letter),
+            (Some(_), None) => false,
+        }
+    }
+    
+    fn matches_extension(&self, filename: &str) -> bool {
+        if self.extensions.is_empty() {
             return true;
         }
         
-        for pattern in &self.file_patterns {
-            if pattern.starts_with("*.") && filename.ends_with(&pattern[1..]) {
-                return true;
-            }
-            else if pattern.contains('*') {
-                let regex_pattern = pattern
-                    .replace(".", "\\.")
-                    .replace("*", ".*");
-                
-                if let Ok(regex) = Regex::new(&regex_pattern) {
-                    if regex.is_match(filename) {
-                        return true;
-                    }
-                }
-            }
-            else if filename == pattern {
-                return true;
-            }
+        let basename = filename.rsplit('/').next().unwrap_or(filename);
+        basename.rsplit_once('.')
+            .is_some_and(|(_, extension)| self.extensions.iter().any(|allowed| allowed == extension))
+    }
+    
+    // Every path filter in one place, so tracked files and diffs see the same set as history.
+    fn matches_file_pattern(&self, filename: &str) -> bool {
+        self.matches_extension(filename)
+            && self.matches_include_pattern(filename)
+            && !self.exclude_paths.iter().any(|pattern| Self::pattern_matches(pattern, filename))
+    }
+    
+    fn matches_include_pattern(&self, filename: &str) -> bool {
+        self.file_patterns.is_empty()
+            || self.file_patterns.iter().any(|pattern| Self::pattern_matches(pattern, filename))
+    }
+    
+    /// Matches `filename` against test-path patterns (defaults cover common `tests/` layouts and
+    /// `_test`/`.spec` naming). Patterns without a `/` are matched against the base name.
+    pub fn is_test_file(filename: &str, test_patterns: Option<&[String]>) -> bool {
+        let basename = filename.rsplit('/').next().unwrap_or(filename);
+        let matches = |pattern: &str| {
+            Self::pattern_matches(pattern, if pattern.contains('/') { filename } else { basename })
+        };
+        
+        match test_patterns {
+            Some(patterns) => patterns.iter().any(|pattern| matches(pattern)),
+            None => DEFAULT_TEST_PATTERNS.iter().any(|pattern| matches(pattern)),
+        }
+    }
+    
+    fn pattern_matches(pattern: &str, filename: &str) -> bool {
+        if pattern.ends_with('/') {
+            filename.starts_with(pattern)
+        }
+        else if pattern.contains('*') {
+            let regex_pattern = format!("^{}$", regex::escape(pattern)
+                .replace("\\*\\*/", "(?:.*/)?")
+                .replace("\\*\\*", ".*")
+                .replace("\\*", "[^/]*"));
+
+            Regex::new(&regex_pattern).is_ok_and(|regex| regex.is_match(filename))
+        }
+        else {
+            filename == pattern
+        }
+    }
+    
+    // A parser bug on one odd record becomes a skipped commit instead of unwinding through rayon
+    // and aborting the whole collection.
+    fn parse_record(&self, commit_data: &str) -> Result<Commit> {
+        catch_parse_panic(commit_data, || self.parse_single_commit(commit_data))
+    }
+    
+    fn parse_single_commit(&self, commit_data: &str) -> Result<Commit> {
+        let mut lines = commit_data.lines();
+        let malformed = |reason: &str| GitMetricsError::MalformedCommit {
+            hash: commit_data.lines().next()
+                .filter(|hash| !hash.is_empty())
+                .map_or_else(|| "<unknown>".to_string(), |hash| hash.to_string()),
+            reason: reason.to_string(),
+        };
+        
+        // One pass over the record: header, then body up to the end marker, then the file section.
+        let header_len = self.header_len();
+        let end_marker = self.end_marker();
+        let mut header = [""; HEADER_LINES + 1 + SIGNATURE_LINES];
+        for slot in header[..header_len].iter_mut() {
+            match lines.next() {
+                Some(line) if line == end_marker => return Err(malformed("not enough header lines")),
+                Some(line) => *slot = line,
+                None => return Err(malformed("no end marker")),
+            }
+        }
+        
+        let mut body = String::new();
+        loop {
+            match lines.next() {
+                Some(line) if line == end_marker => break,
+                Some(line) => {
+                    body.push_str(line);
+                    body.push('\n');
+                },
+                None => return Err(malformed("no end marker")),
+            }
+        }
+        
+        let commit_hash = header[0].to_string();
+        let short_hash = header[1].to_string();
+        let author = header[2].to_string();
+        let author_email = self.author_aliases.get(header[3]).map_or(header[3], String::as_str).to_string();
+        let date = header[4].to_string();
+        let timestamp = header[5].parse::<i64>()
+            .map_err(|e| malformed(&format!("invalid timestamp '{}': {}", header[5], e)))?;
+        let author_date = DateTime::parse_from_rfc3339(header[6])
+            .map_err(|e| malformed(&format!("invalid author date '{}': {}", header[6], e)))?;
+        let date_utc = author_date.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true);
+        let utc_offset_minutes = author_date.offset().local_minus_utc() / 60;
+        let parents: Vec<String> = header[7].split_whitespace().map(str::to_string).collect();
+        let is_root = parents.is_empty();
+        let tree_hash = header[8].to_string();
+        let encoding = Some(header[9].to_string()).filter(|encoding| self.capture_encoding && !encoding.is_empty());
+        let signature_start = 9 + usize::from(self.capture_encoding);
+        let captured = |line: &str| Some(line.to_string()).filter(|_| self.capture_signatures);
+        let signature_status = captured(header[signature_start]);
+        let signer = captured(header[signature_start + 1]).filter(|signer| !signer.is_empty());
+        let signing_key = captured(header[signature_start + 2]).filter(|key| !key.is_empty());
+        let message = header[header_len - 1].to_string();
+        let body = body.trim().to_string();
+        let reverted_hash = parse_reverted_hash(&body);
+        let trailers = parse_trailers(&body);
+        
+        let (files, has_line_changes) = self.parse_file_changes(&commit_hash, lines)?;
+        let count_status = |letter: char| files.iter()
+            .filter(|file| file.status.starts_with(letter))
+            .count();
+        let is_code_change = files.iter().any(|file| self.is_code_file(&file.filename));
+        
+        Ok(Commit {
+            hash: commit_hash,
+            short_hash,
+            author,
+            author_email,
+            date,
+            timestamp,
+            date_utc,
+            utc_offset_minutes,
+            parents,
+            is_root,
+            tree_hash,
+            is_code_change,
+            is_whitespace_only: false,
+            has_line_changes,
+            message,
+            body,
+            reverted_hash,
+            trailers,
+            signature_status,
+            signer,
+            signing_key,
+            encoding,
+            files_added: count_status('A'),
+            files_modified: count_status('M'),
+            files_deleted: count_status('D'),
+            files_renamed: count_status('R'),
+            files,
+            index: 0,
+        })
+    }
+    
+    fn parse_file_changes<'a>(&self, hash: &str, file_lines: impl Iterator<Item = &'a str>) -> Result<(Vec<FileChange>, bool)> {
+        let mut entries = Vec::new();
+        let mut line_counts = Vec::new();
+        
+        for line in file_lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            
+            if let Some(raw) = line.strip_prefix(':') {
+                // Combined diffs of merge commits use one leading colon per parent.
+                let from_merge = raw.starts_with(':');
+                let parts: Vec<&str> = raw.split('\t').collect();
+                if parts.len() < 2 {
+                    continue;
+                }
+                
+                let fields: Vec<&str> = parts[0].split_whitespace().collect();
+                let status = fields.last().copied().unwrap_or_default();
+                // Modes come first, one per parent plus the result; symlinks use mode 120000.
+                let mode_count = raw.len() - raw.trim_start_matches(':').len() + 2;
+                let is_symlink = fields.iter().take(mode_count).any(|mode| *mode == SYMLINK_MODE);
+                let (old_filename, filename) = match parts.get(2) {
+                    Some(new_name) => (Some(parts[1]), *new_name),
+                    None => (None, parts[1]),
+                };
+                entries.push((status, old_filename, filename, from_merge, is_symlink));
+            } else {
+                let parts: Vec<&str> = line.splitn(3, '\t').collect();
+                if parts.len() < 3 {
+                    continue;
+                }
+                
+                // Binary files report "-" for both counts.
+                line_counts.push(parts[0].parse::<u32>().ok().zip(parts[1].parse::<u32>().ok()));
+            }
+        }
+        
+        // Under `--cc` numstat is against the first parent and lists files that merged cleanly,
+        // so it does not line up with the combined raw entries.
+        let merge_counts = if entries.iter().any(|entry| entry.3) {
+            self.combined_diff_counts(hash)?
+        } else {
+            HashMap::new()
+        };
+        
+        let mut files = Vec::new();
+        let mut has_line_changes = false;
+        
+        for (i, (status, old_filename, filename, from_merge, is_symlink)) in entries.into_iter().enumerate() {
+            if !self.matches_file_pattern(filename) || !self.matches_status_filter(status) {
+                continue;
+            }
+            
+            let counts = if from_merge { merge_counts.get(filename).copied() } else { line_counts.get(i).copied() };
+            let numstat = counts.flatten();
+            // The real counts, not the status fallback: a pure rename reports "0\t0".
+            has_line_changes |= match counts {
+                Some(Some((additions, deletions))) => additions + deletions > 0,
+                Some(None) => true,
+                None => false,
+            };
+            let is_rename_or_copy = status.starts_with('R') || status.starts_with('C');
+            
+            let (raw_additions, raw_deletions) = match numstat {
+                Some(counts) if !is_rename_or_copy || self.normalize_rename_churn => counts,
+                _ => self.status_to_change_count(status),
+            };
+            let cap = self.max_file_lines.unwrap_or(u32::MAX);
+            // A symlink's "content" is its target path, which isn't churn.
+            let (additions, deletions) = if is_symlink {
+                (0, 0)
+            } else {
+                (raw_additions.min(cap), raw_deletions.min(cap))
+            };
+            
+            files.push(FileChange {
+                filename: self.strip_prefix(filename),
+                old_filename: old_filename.map(|old_filename| self.strip_prefix(old_filename)),
+                status: status.to_string(),
+                additions,
+                deletions,
+                binary: counts.is_some_and(|counts| counts.is_none()),
+                from_merge,
+                capped: !is_symlink && (raw_additions > cap || raw_deletions > cap),
+                raw_additions,
+                raw_deletions,
+                is_symlink,
+            });
+        }
+        
+        Ok((files, has_line_changes))
+    }
+    
+    /// Per-file counts from a merge's combined patch, which has one marker column per parent.
+    /// Lines added against every parent are additions; lines dropped from any parent are
+    /// deletions. Binary files map to `None`, like numstat's `-`.
+    fn combined_diff_counts(&self, hash: &str) -> Result<HashMap<String, Option<(u32, u32)>>> {
+        let output = self.run_git_command(&["show", "--cc", "--format=", "--no-ext-diff", "--no-textconv", hash, "--"])?;
+        Ok(parse_combined_diff(&output))
+    }
+    
+    fn status_to_change_count(&self, status: &str) -> (u32, u32) {
+        match status.chars().next() {
+            Some('A') => (1, 0),
+            Some('D') => (0, 1),
+            Some('M') | Some('R') | Some('C') => (1, 1),
+            _ => (0, 0),
+        }
+    }
+    
+    pub fn get_current_changes(&self, mode: DiffMode) -> Result<HashMap<String, HashMap<String, u32>>> {
+        info!("Analyzing current changes...");
+        
+        let diff_output = match mode {
+            DiffMode::Unstaged => self.run_git_command(&["diff", "--numstat", "--no-renames"])?,
+            DiffMode::Staged => self.run_git_command(&["diff", "--cached", "--numstat", "--no-renames"])?,
+            DiffMode::Both => self.run_git_command(&["diff", "HEAD", "--numstat", "--no-renames"])?,
+        };
+        debug!("Git diff output: {}", diff_output);
+        
+        let changes = self.collect_diff_changes(&diff_output);
+        
+        info!("Analyzed {} changes", changes.len());
+        Ok(changes)
+    }
+    
+    pub fn diff_summary(&self, mode: DiffMode) -> Result<DiffSummary> {
+        let output = match mode {
+            DiffMode::Unstaged => self.run_git_command(&["diff", "--shortstat"])?,
+            DiffMode::Staged => self.run_git_command(&["diff", "--cached", "--shortstat"])?,
+            DiffMode::Both => self.run_git_command(&["diff", "HEAD", "--shortstat"])?,
+        };
+        Ok(parse_shortstat(&output))
+    }
+    
+    pub fn working_tree_diff(&self, reference: &str) -> Result<HashMap<String, HashMap<String, u32>>> {
+        let commit_rev = format!("{}^{{commit}}", reference);
+        if self.run_git_command(&["rev-parse", "--verify", "--quiet", &commit_rev]).is_err() {
+            return Err(GitMetricsError::Other(format!("Unknown ref: {}", reference)));
+        }
+        
+        let diff_output = self.run_git_command(&["diff", "--numstat", "--no-renames", &commit_rev, "--"])?;
+        Ok(self.collect_diff_changes(&diff_output))
+    }
+    
+    fn collect_diff_changes(&self, diff_output: &str) -> HashMap<String, HashMap<String, u32>> {
+        let mut changes = HashMap::new();
+        
+        for line in diff_output.lines() {
+            if let Some(file_changes) = self.parse_numstat_line(line) {
+                changes.insert(file_changes.0, file_changes.1);
+            }
+        }
+        
+        changes
+    }
+    
+    fn parse_numstat_line(&self, line: &str) -> Option<(String, HashMap<String, u32>)> {
+        let parts: Vec<&str> = line.splitn(3, '\t').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        
+        let filename = parts[2].trim().to_string();
+        
+        if !self.matches_file_pattern(&filename) {
+            return None;
+        }
+        
+        let insertions = parts[0].parse::<u32>().unwrap_or(0);
+        let deletions = parts[1].parse::<u32>().unwrap_or(0);
+        
+        Some((filename, change_stats(insertions, deletions)))
+    }
+}
+
+pub struct CommitStream {
+    collector: GitCollector,
+    child: Child,
+    reader: BufReader<ChildStdout>,
+    stderr_reader: Option<thread::JoinHandle<Vec<u8>>>,
+    record: String,
+    decoder: RecordDecoder,
+    records_read: usize,
+    yielded: usize,
+    finished: bool,
+}
+
+impl CommitStream {
+    fn next_record(&mut self) -> Result<Option<String>> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = self.reader.read_until(b'\n', &mut line)
+                .map_err(GitMetricsError::IoError)?;
+            
+            if read == 0 {
+                if self.record.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(std::mem::take(&mut self.record)));
+            }
+            
+            let line = self.decoder.decode_line(&line);
+            if line == self.decoder.start_marker {
+                let record = std::mem::take(&mut self.record);
+                if !record.is_empty() {
+                    return Ok(Some(record));
+                }
+            } else {
+                self.record.push_str(&line);
+            }
+        }
+    }
+    
+    fn finish(&mut self) -> Result<()> {
+        self.finished = true;
+        let status = self.child.wait().map_err(GitMetricsError::IoError)?;
+        let stderr = self.stderr_reader.take()
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+        
+        if !status.success() {
+            return Err(GitMetricsError::CommandError(
+                format!("Git command failed: {}", String::from_utf8_lossy(&stderr))
+            ));
+        }
+        
+        self.collector.warn_unmatched_patterns();
+        Ok(())
+    }
+    
+    fn stop(&mut self) {
+        self.finished = true;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Iterator for CommitStream {
+    type Item = Result<Commit>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            if self.collector.hard_commit_cap.is_some_and(|cap| self.yielded >= cap) {
+                debug!("Hard commit cap reached after {} commits, stopping git", self.yielded);
+                self.stop();
+                return None;
+            }
+            
+            let record = match self.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => return self.finish().err().map(Err),
+                Err(e) => {
+                    self.stop();
+                    return Some(Err(e));
+                }
+            };
+            
+            let index = self.records_read;
+            self.records_read += 1;
+            
+            match self.collector.parse_record(&record) {
+                Ok(commit) => {
+                    if self.collector.keeps_parsed_commit(&commit) && !self.collector.is_excluded_author(&commit) {
+                        self.yielded += 1;
+                        self.collector.record_pattern_hits([&commit]);
+                        return Some(Ok(Commit { index, ..commit }));
+                    }
+                },
+                Err(e) => warn!("Skipping commit record: {}", e),
+            }
+        }
+        None
+    }
+}
+
+impl Drop for CommitStream {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.stop();
+        }
+    }
+}
+
+pub fn collect_repositories<F>(
+    repo_paths: &[String],
+    max_parallel: usize,
+    build_collector: F
+) -> Result<Vec<(String, Result<Vec<Commit>>)>>
+where
+    F: Fn(&str) -> GitCollector + Sync,
+{
+    info!("Collecting history for {} repositories ({} at a time)...", repo_paths.len(), max_parallel.max(1));
+    map_repositories(repo_paths, max_parallel, build_collector, GitCollector::collect_history)
+}
+
+pub fn map_repositories<T, F, G>(
+    repo_paths: &[String],
+    max_parallel: usize,
+    build_collector: F,
+    run: G
+) -> Result<Vec<(String, Result<T>)>>
+where
+    T: Send,
+    F: Fn(&str) -> GitCollector + Sync,
+    G: Fn(&GitCollector) -> Result<T> + Sync,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(max_parallel.max(1))
+        .build()
+        .map_err(|e| GitMetricsError::Other(format!("Failed to create thread pool: {}", e)))?;
+    
+    Ok(pool.install(|| {
+        repo_paths.par_iter()
+            .map(|repo_path| (repo_path.clone(), run(&build_collector(repo_path))))
+            .collect()
+    }))
+}
+
+fn encode_commits(commits: &[Commit], format: CacheFormat) -> Result<Vec<u8>> {
+    match format {
+        CacheFormat::Json => Ok(serde_json::to_vec(commits)?),
+        // Binary payloads carry the schema version since their layout is positional.
+        CacheFormat::Bincode => Ok(bincode::serialize(&(CACHE_SCHEMA_VERSION, commits))?),
+    }
+}
+
+fn decode_commits(data: &[u8], format: CacheFormat) -> Result<Vec<Commit>> {
+    match format {
+        CacheFormat::Json => Ok(serde_json::from_slice(data)?),
+        CacheFormat::Bincode => {
+            let (schema_version, commits): (u32, Vec<Commit>) = bincode::deserialize(data)?;
+            if schema_version != CACHE_SCHEMA_VERSION {
+                return Err(GitMetricsError::Other(format!(
+                    "Binary data has schema version {}, expected {}", schema_version, CACHE_SCHEMA_VERSION
+                )));
+            }
+            Ok(commits)
+        }
+    }
+}
+
+// " 3 files changed, 1 insertion(+), 12 deletions(-)"; git omits zero counts and prints
+// nothing at all for an empty diff.
+fn parse_shortstat(output: &str) -> DiffSummary {
+    let mut summary = DiffSummary::default();
+    for part in output.trim().split(", ") {
+        let Some((count, label)) = part.trim().split_once(' ') else {
+            continue;
+        };
+        let Ok(count) = count.parse::<u32>() else {
+            continue;
+        };
+        
+        if label.starts_with("file") {
+            summary.files_changed = count;
+        } else if label.starts_with("insertion") {
+            summary.insertions = count;
+        } else if label.starts_with("deletion") {
+            summary.deletions = count;
+        }
+    }
+    summary
+}
+
+/// Decodes `git log` output line by line. Message lines that are not valid UTF-8 are decoded with
+/// the encoding their record declares; everything else is read as UTF-8.
+struct RecordDecoder {
+    encoding_line: Option<usize>,
+    message_line: usize,
+    position: Option<usize>,
+    encoding: Option<&'static Encoding>,
+    start_marker: String,
+    end_marker: String,
+}
+
+impl RecordDecoder {
+    fn decode_line<'a>(&mut self, line: &'a [u8]) -> Cow<'a, str> {
+        if line == self.start_marker.as_bytes() {
+            self.position = Some(0);
+            self.encoding = None;
+            return String::from_utf8_lossy(line);
+        }
+        if line.strip_suffix(b"\n").unwrap_or(line) == self.end_marker.as_bytes() {
+            self.position = None;
+            return String::from_utf8_lossy(line);
+        }
+        
+        let Some(position) = self.position else {
+            return String::from_utf8_lossy(line);
+        };
+        self.position = Some(position + 1);
+        
+        if Some(position) == self.encoding_line {
+            let label = String::from_utf8_lossy(line);
+            self.encoding = Encoding::for_label(label.trim().as_bytes());
+        }
+        match (std::str::from_utf8(line), self.encoding) {
+            (Ok(line), _) => Cow::Borrowed(line),
+            (Err(_), Some(encoding)) if position >= self.message_line => encoding.decode_without_bom_handling(line).0,
+            (Err(_), _) => String::from_utf8_lossy(line),
+        }
+    }
+}
+
+fn catch_parse_panic(commit_data: &str, parse: impl FnOnce() -> Result<Commit>) -> Result<Commit> {
+    panic::catch_unwind(AssertUnwindSafe(parse)).unwrap_or_else(|payload| {
+        let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        
+        Err(GitMetricsError::MalformedCommit {
+            hash: commit_data.lines().next()
+                .filter(|hash| !hash.is_empty())
+                .map_or_else(|| "<unknown>".to_string(), |hash| hash.to_string()),
+            reason: format!("parser panicked: {}", message),
+        })
+    })
+}
+
+fn parse_git_version(output: &str) -> Option<(u32, u32, u32)> {
+    let version = output.trim().strip_prefix("git version ")?;
+    let mut numbers = version.split(|c: char| !c.is_ascii_digit())
+        .map(|part| part.parse::<u32>().ok());
+    
+    let major = numbers.next()??;
+    let minor = numbers.next()??;
+    let patch = numbers.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+fn split_batch_output(output: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut blobs = Vec::new();
+    let mut rest = output;
+    
+    while !rest.is_empty() {
+        let header_end = rest.iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| GitMetricsError::Other("Truncated git cat-file output".to_string()))?;
+        let header = String::from_utf8_lossy(&rest[..header_end]);
+        rest = &rest[header_end + 1..];
+        
+        if header.ends_with(" missing") {
+            blobs.push(&rest[..0]);
+            continue;
+        }
+        
+        let size = header.rsplit(' ')
+            .next()
+            .and_then(|size| size.parse::<usize>().ok())
+            .filter(|size| *size <= rest.len())
+            .ok_or_else(|| GitMetricsError::Other(format!("Unexpected git cat-file header: {}", header)))?;
+        
+        blobs.push(&rest[..size]);
+        rest = rest.get(size + 1..).unwrap_or_default();
+    }
+    
+    Ok(blobs)
+}
+
+// Numstat writes renames as "old => new" or "dir/{old => new}/rest"; this keeps the new side.
+// A regex matching `text` literally and ignoring ASCII case for git's `--author`. Punctuation
+// goes in brackets, which reads the same whatever `grep.patternType` the user configured.
+fn case_insensitive_pattern(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphabetic() => format!("[{}{}]", c.to_ascii_lowercase(), c.to_ascii_uppercase()),
+            '^' | ']' | '\\' => format!("\\{}", c),
+            c if c.is_ascii_punctuation() => format!("[{}]", c),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn numstat_new_path(path: &str) -> String {
+    let braced = path.split_once('{').and_then(|(prefix, rest)| {
+        let (renamed, suffix) = rest.split_once('}')?;
+        let (_, new) = renamed.split_once(" => ")?;
+        Some(format!("{}{}{}", prefix, new, suffix).replace("//", "/"))
+    });
+    braced.unwrap_or_else(|| path.split_once(" => ").map_or(path, |(_, new)| new).to_string())
+}
+
+fn parse_combined_diff(output: &str) -> HashMap<String, Option<(u32, u32)>> {
+    let mut counts = HashMap::new();
+    let mut current: Option<&mut Option<(u32, u32)>> = None;
+    let mut columns = 0;
+    
+    for line in output.lines() {
+        let markers = line.get(..columns).filter(|markers| {
+            columns > 0 && markers.chars().all(|marker| matches!(marker, ' ' | '+' | '-'))
+        });
+        match (markers, current.as_deref_mut()) {
+            (Some(markers), Some(Some((additions, deletions)))) => {
+                if markers.chars().all(|marker| marker == '+') {
+                    *additions += 1;
+                } else if markers.contains('-') {
+                    *deletions += 1;
+                }
+                continue;
+            },
+            (Some(_), _) => continue,
+            (None, _) => {},
+        }
+        
+        if let Some(path) = line.strip_prefix("diff --cc ") {
+            current = Some(counts.entry(path.to_string()).or_insert(Some((0, 0))));
+            columns = 0;
+        } else if line.starts_with("@@@") {
+            // A merge with N parents has hunk headers opening with N + 1 '@'.
+            columns = line.len() - line.trim_start_matches('@').len() - 1;
+        } else if line.starts_with("Binary files ") {
+            if let Some(file_counts) = current.as_deref_mut() {
+                *file_counts = None;
+            }
+        }
+    }
+    
+    counts
+}
+
+fn change_stats(insertions: u32, deletions: u32) -> HashMap<String, u32> {
+    let mut file_changes = HashMap::new();
+    file_changes.insert("additions".to_string(), insertions);
+    file_changes.insert("deletions".to_string(), deletions);
+    file_changes.insert("total".to_string(), insertions + deletions);
+    file_changes
+}
+
+// Follows `git interpret-trailers`: only the last paragraph can hold trailers, and it counts
+// when every line is a trailer, or when at least a quarter are and one of them is one git
+// itself generates. Indented lines continue the previous trailer's value.
+fn parse_trailers(body: &str) -> HashMap<String, Vec<String>> {
+    let lines: Vec<&str> = body.trim_end().lines().collect();
+    let start = lines.iter().rposition(|line| line.trim().is_empty()).map_or(0, |blank| blank + 1);
+    
+    let mut entries: Vec<(&str, String)> = Vec::new();
+    let (mut trailer_lines, mut other_lines, mut git_generated) = (0, 0, false);
+    let mut continues = false;
+    for line in &lines[start..] {
+        if let Some((key, value)) = trailer_line(line) {
+            git_generated |= key.eq_ignore_ascii_case("Signed-off-by");
+            entries.push((key, value.to_string()));
+            trailer_lines += 1;
+            continues = true;
+        } else if line.starts_with("(cherry picked from commit ") {
+            git_generated = true;
+            trailer_lines += 1;
+            continues = false;
+        } else if continues && line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = entries.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else {
+            other_lines += 1;
+            continues = false;
+        }
+    }
+    
+    let mut trailers: HashMap<String, Vec<String>> = HashMap::new();
+    if trailer_lines > 0 && (other_lines == 0 || (git_generated && trailer_lines * 3 >= other_lines)) {
+        for (key, value) in entries {
+            trailers.entry(key.to_string()).or_default().push(value);
+        }
+    }
+    trailers
+}
+
+fn trailer_line(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim_end();
+    let is_token = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    is_token.then(|| (key, value.trim()))
+}
+
+fn parse_reverted_hash(body: &str) -> Option<String> {
+    static REVERT_REGEX: OnceLock<Regex> = OnceLock::new();
+    let regex = REVERT_REGEX.get_or_init(|| {
+        Regex::new(r"This reverts commit ([0-9a-f]{7,40})").unwrap()
+    });
+    
+    regex.captures(body).map(|captures| captures[1].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+    
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .current_dir(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+    
+    fn init_test_repo() -> TempDir {
+        let dir = tempdir().unwrap();
+        git(dir.path(), &["init", "-q"]);
+        dir
+    }
+    
+    fn commit_file(dir: &Path, filename: &str, content: &str, message: &str) {
+        let path = dir.join(filename);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, content).unwrap();
+        git(dir, &["add", "-A"]);
+        git(dir, &["commit", "-q", "-m", message]);
+    }
+    
+    #[test]
+    fn test_status_to_change_count() {
+        let collector = GitCollector::new(".", None, None, Vec::new());
+        
+        assert_eq!(collector.status_to_change_count("A"), (1, 0));
+        assert_eq!(collector.status_to_change_count("D"), (0, 1));
+        assert_eq!(collector.status_to_change_count("M"), (1, 1));
+        assert_eq!(collector.status_to_change_count("R100"), (1, 1));
+        assert_eq!(collector.status_to_change_count("??"), (0, 0));
+    }
+    
+    #[test]
+    fn test_matches_file_pattern() {
+        let patterns = vec![
+            "*.rs".to_string(),
+            "src/*".to_string(),
+            "exact_file.txt".to_string()
+        ];
+        
+        let collector = GitCollector::new(".", None, None, patterns);
+        
+        assert!(collector.matches_file_pattern("main.rs"));
+        assert!(collector.matches_file_pattern("src/lib.rs"));
+        assert!(collector.matches_file_pattern("exact_file.txt"));
+        
+        assert!(!collector.matches_file_pattern("main.js"));
+        assert!(!collector.matches_file_pattern("test/main.rs"));
+        assert!(!collector.matches_file_pattern("exact_file_2.txt"));
+    }
+    
+    #[test]
+    fn test_matches_directory_prefix_pattern() {
+        let collector = GitCollector::new(".", None, None, vec!["src/".to_string()]);
+        
+        assert!(collector.matches_file_pattern("src/lib.rs"));
+        assert!(collector.matches_file_pattern("src/a/b.rs"));
+        
+        assert!(!collector.matches_file_pattern("src"));
+        assert!(!collector.matches_file_pattern("srcs/lib.rs"));
+        assert!(!collector.matches_file_pattern("test/src/lib.rs"));
+    }
+    
+    #[test]
+    fn test_read_patterns_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("patterns.txt");
+        fs::write(&path, "# sources\n*.rs\n\n  src/*  \n# docs\nREADME.md\n").unwrap();
+        
+        let patterns = GitCollector::read_patterns_file(path.to_str().unwrap()).unwrap();
+        
+        assert_eq!(patterns, vec!["*.rs", "src/*", "README.md"]);
+        assert!(GitCollector::read_patterns_file("/nonexistent/patterns.txt").is_err());
+    }
+    
+    #[test]
+    fn test_file_pattern_rules() {
+        let matches = |pattern: &str, filename: &str| GitCollector::pattern_matches(pattern, filename);
+        
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(!matches("*.rs", "main.rs.orig"));
+        assert!(matches("**/*.rs", "main.rs"));
+        assert!(matches("**/*.rs", "src/bin/main.rs"));
+        assert!(matches("src/**/mod.rs", "src/mod.rs"));
+        assert!(matches("src/**", "src/a/b.txt"));
+        assert!(matches("vendor/", "vendor/dep/lib.rs"));
+        
+        let by_extension = GitCollector::new(".", None, None, Vec::new()).with_extensions(vec!["rs".to_string()]);
+        for filename in ["main.rs", "src/bin/main.rs", "main.rs.orig", "README.md"] {
+            assert_eq!(by_extension.matches_extension(filename), matches("**/*.rs", filename), "{}", filename);
+        }
+    }
+    
+    #[test]
+    fn test_parse_single_commit_reports_hash() {
+        let collector = GitCollector::new(".", None, None, Vec::new());
+        
+        let err = collector.parse_single_commit("abc123\nabc\nTest\ntest@example.com\nnow\nnot-a-number\n2024-01-01T00:00:00Z\nparent\ntree\nsubject\nCOMMIT_END\n")
+            .unwrap_err();
+        match err {
+            GitMetricsError::MalformedCommit { hash, .. } => assert_eq!(hash, "abc123"),
+            other => panic!("unexpected error: {}", other),
+        }
+        
+        assert!(matches!(
+            collector.parse_single_commit("abc123\nTest\n"),
+            Err(GitMetricsError::MalformedCommit { .. })
+        ));
+    }
+    
+    #[test]
+    fn test_parse_panic_becomes_malformed_commit() {
+        let record = "abc123\nsubject\nCOMMIT_END\n";
+        let err = catch_parse_panic(record, || {
+            let header: Vec<&str> = record.lines().collect();
+            Ok(Commit { message: header[12].to_string(), ..Default::default() })
+        })
+        .unwrap_err();
+        match err {
+            GitMetricsError::MalformedCommit { hash, reason } => {
+                assert_eq!(hash, "abc123");
+                assert!(reason.contains("index out of bounds"), "{}", reason);
+            },
+            other => panic!("unexpected error: {}", other),
+        }
+        
+        let collector = GitCollector::new(".", None, None, Vec::new());
+        let data = "COMMIT_START\nabc\nCOMMIT_END\nCOMMIT_START\n\n\n\n\n\n\n\n\n\n\n\n";
+        assert!(collector.parse_commit_data(data).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_parse_single_commit_with_many_files() {
+        let collector = GitCollector::new(".", None, None, Vec::new());
+        let mut record = "abc\na\nTest\ntest@example.com\nnow\n100\n1970-01-01T00:01:40Z\nparent\ntree\nsubject\n\nbody\nCOMMIT_END\n".to_string();
+        for i in 0..5000 {
+            record.push_str(&format!(":100644 100644 1111111 2222222 M\tsrc/file{}.rs\n", i));
+        }
+        for i in 0..5000 {
+            record.push_str(&format!("{}\t1\tsrc/file{}.rs\n", i % 7, i));
+        }
+        
+        let commit = collector.parse_single_commit(&record).unwrap();
+        
+        assert_eq!(commit.body, "body");
+        assert_eq!(commit.files.len(), 5000);
+        assert_eq!(commit.files_modified, 5000);
+        assert_eq!(commit.files[4999].filename, "src/file4999.rs");
+        assert_eq!(commit.files[4999].additions, 4999 % 7);
+        assert_eq!(commit.files.iter().map(|file| file.deletions).sum::<u32>(), 5000);
+    }
+    
+    #[test]
+    fn test_record_markers() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        fs::write(repo.path().join("a.txt"), "two").unwrap();
+        git(repo.path(), &["commit", "-q", "-am", "tricky\n\nCOMMIT_END\nCOMMIT_START\nstill the body"]);
+        let repo_path = repo.path().to_str().unwrap();
+        let markers = || Some(("GITSECT_BEGIN_7f3a".to_string(), "GITSECT_END_7f3a".to_string()));
+        
+        let commits = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_record_markers(markers())
+            .collect_history()
+            .unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].body, "COMMIT_END\nCOMMIT_START\nstill the body");
+        assert_eq!(commits[0].files[0].filename, "a.txt");
+        
+        let streamed: Vec<Commit> = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_record_markers(markers())
+            .stream_commits()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(streamed[0].body, commits[0].body);
+        
+        let collector = GitCollector::new(".", None, None, Vec::new()).with_record_markers(markers());
+        let data = "GITSECT_BEGIN_7f3a\nabc\na\nTest\ntest@example.com\nnow\n100\n1970-01-01T00:01:40Z\n\ntree\nsubject\nCOMMIT_END\nGITSECT_END_7f3a\n";
+        assert_eq!(collector.parse_commit_data(data).unwrap()[0].body, "COMMIT_END");
+        
+        for (start, end) in [("A%n", "B"), ("SAME", "SAME"), ("", "END")] {
+            let invalid = GitCollector::new(repo_path, None, None, Vec::new())
+                .with_record_markers(Some((start.to_string(), end.to_string())));
+            assert!(invalid.plan().is_err(), "{:?} accepted", (start, end));
+        }
+    }
+    
+    #[test]
+    fn test_parse_commit_data_skips_malformed() {
+        let collector = GitCollector::new(".", None, None, Vec::new());
+        let data = "COMMIT_START\ndef\nTest\nCOMMIT_END\n\n\
+                    COMMIT_START\nabc\na\nTest\ntest@example.com\nnow\n100\n1970-01-01T02:01:40+02:00\n\ntree\nsubject\nbody line\nCOMMIT_END\n\
+                    :100644 100644 1111111 2222222 M\tsrc/lib.rs\n3\t1\tsrc/lib.rs\n";
+        
+        let commits = collector.parse_commit_data(data).unwrap();
+        
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].hash, "abc");
+        assert_eq!(commits[0].timestamp, 100);
+        assert_eq!(commits[0].index, 1);
+        assert_eq!(commits[0].date_utc, "1970-01-01T00:01:40Z");
+        assert_eq!(commits[0].utc_offset_minutes, 120);
+        assert!(commits[0].parents.is_empty());
+        assert!(commits[0].is_root);
+        assert_eq!(commits[0].tree_hash, "tree");
+        assert_eq!(commits[0].files.len(), 1);
+        assert_eq!(commits[0].files[0].filename, "src/lib.rs");
+        assert_eq!(commits[0].files[0].additions, 3);
+        assert_eq!(commits[0].files[0].deletions, 1);
+        assert_eq!(commits[0].files_modified, 1);
+        assert_eq!(commits[0].files_added, 0);
+    }
+    
+    #[test]
+    fn test_normalize_rename_churn() {
+        let repo = init_test_repo();
+        let content = "line one\nline two\nline three\nline four\nline five\n";
+        commit_file(repo.path(), "pure.txt", "pure one\npure two\npure three\n", "add pure");
+        commit_file(repo.path(), "edited.txt", content, "add edited");
+        git(repo.path(), &["mv", "pure.txt", "pure_renamed.txt"]);
+        git(repo.path(), &["mv", "edited.txt", "edited_renamed.txt"]);
+        commit_file(repo.path(), "edited_renamed.txt", &format!("{}line six\n", content), "rename both");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let find = |commit: &Commit, name: &str| commit.files.iter()
+            .find(|file| file.filename == name)
+            .cloned()
+            .unwrap();
+        
+        let heuristic = GitCollector::new(repo_path, None, None, Vec::new())
+            .collect_history()
+            .unwrap();
+        let pure = find(&heuristic[0], "pure_renamed.txt");
+        assert_eq!(pure.status.chars().next(), Some('R'));
+        assert_eq!(pure.old_filename.as_deref(), Some("pure.txt"));
+        assert_eq!((pure.additions, pure.deletions), (1, 1));
+        
+        let normalized = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_normalize_rename_churn(true)
+            .collect_history()
+            .unwrap();
+        let pure = find(&normalized[0], "pure_renamed.txt");
+        let edited = find(&normalized[0], "edited_renamed.txt");
+        assert_eq!((pure.additions, pure.deletions), (0, 0));
+        assert_eq!((edited.additions, edited.deletions), (1, 0));
+    }
+    
+    #[test]
+    fn test_max_file_lines() {
+        let repo = init_test_repo();
+        let generated: String = (0..50).map(|n| format!("generated {}\n", n)).collect();
+        fs::write(repo.path().join("small.txt"), "one\ntwo\n").unwrap();
+        commit_file(repo.path(), "bundle.js", &generated, "add bundle");
+        
+        let commits = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_max_file_lines(Some(10))
+            .collect_history()
+            .unwrap();
+        let find = |name: &str| commits[0].files.iter().find(|file| file.filename == name).unwrap();
+        
+        let bundle = find("bundle.js");
+        assert!(bundle.capped);
+        assert_eq!((bundle.additions, bundle.raw_additions), (10, 50));
+        let small = find("small.txt");
+        assert!(!small.capped);
+        assert_eq!((small.additions, small.raw_additions), (2, 2));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_changes() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "target.txt", "one\ntwo\n", "add target");
+        std::os::unix::fs::symlink("target.txt", repo.path().join("link.txt")).unwrap();
+        commit_file(repo.path(), "other.txt", "three\n", "add link");
+        fs::remove_file(repo.path().join("link.txt")).unwrap();
+        commit_file(repo.path(), "other.txt", "three\nfour\n", "remove link");
+        
+        let commits = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .collect_history()
+            .unwrap();
+        let find = |commit: &Commit, name: &str| commit.files.iter()
+            .find(|file| file.filename == name)
+            .cloned()
+            .unwrap();
+        
+        let added = find(&commits[1], "link.txt");
+        assert!(added.is_symlink);
+        assert_eq!(added.status, "A");
+        assert_eq!((added.additions, added.deletions), (0, 0));
+        assert_eq!(added.raw_additions, 1);
+        assert!(!find(&commits[1], "other.txt").is_symlink);
+        
+        let removed = find(&commits[0], "link.txt");
+        assert!(removed.is_symlink);
+        assert_eq!((removed.additions, removed.deletions), (0, 0));
+        assert_eq!(commits[0].files.iter().map(|file| file.additions).sum::<u32>(), 1);
+    }
+    
+    #[test]
+    fn test_rename_and_copy_detection() {
+        let repo = init_test_repo();
+        let lines = |changed: usize| (0..10)
+            .map(|n| if n < 10 - changed { format!("shared line {} with some padding\n", n) } else { format!("edit {}\n", n) })
+            .collect::<String>();
+        commit_file(repo.path(), "a.txt", &lines(0), "add a");
+        git(repo.path(), &["mv", "a.txt", "b.txt"]);
+        commit_file(repo.path(), "b.txt", &lines(3), "move and edit");
+        fs::write(repo.path().join("copy.txt"), lines(3)).unwrap();
+        commit_file(repo.path(), "b.txt", &lines(4), "copy and edit");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let statuses = |collector: GitCollector, index: usize| {
+            let commits = collector.collect_history().unwrap();
+            let mut statuses: Vec<char> = commits[index].files.iter()
+                .filter_map(|file| file.status.chars().next())
+                .collect();
+            statuses.sort();
+            statuses
+        };
+        let base = || GitCollector::new(repo_path, None, None, Vec::new());
+        
+        assert_eq!(statuses(base().with_detect_renames(Some(true)).with_rename_threshold(Some(50)), 1), vec!['R']);
+        assert_eq!(statuses(base().with_rename_threshold(Some(90)), 1), vec!['A', 'D']);
+        assert_eq!(statuses(base().with_detect_renames(Some(false)), 1), vec!['A', 'D']);
+        assert_eq!(statuses(base().with_detect_copies(true), 0), vec!['C', 'M']);
+    }
+    
+    #[test]
+    fn test_status_filter() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "add a");
+        commit_file(repo.path(), "a.txt", "two", "modify a");
+        fs::write(repo.path().join("b.txt"), "three").unwrap();
+        commit_file(repo.path(), "a.txt", "four", "add b and modify a");
+        
+        let commits = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_status_filter(Some(vec!['A']))
+            .collect_history()
+            .unwrap();
+        
+        let summary: Vec<(&str, Vec<&str>)> = commits.iter()
+            .map(|commit| (commit.message.as_str(), commit.files.iter().map(|file| file.filename.as_str()).collect()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("add b and modify a", vec!["b.txt"]),
+            ("add a", vec!["a.txt"]),
+        ]);
+    }
+    
+    #[test]
+    fn test_min_files() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.rs", "one", "one file");
+        fs::write(repo.path().join("b.rs"), "two").unwrap();
+        fs::write(repo.path().join("c.txt"), "three").unwrap();
+        commit_file(repo.path(), "a.rs", "four", "three files");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let messages = |collector: GitCollector| collector.collect_history().unwrap()
+            .into_iter()
+            .map(|commit| commit.message)
+            .collect::<Vec<_>>();
+        
+        assert_eq!(messages(GitCollector::new(repo_path, None, None, Vec::new()).with_min_files(Some(3))), vec!["three files"]);
+        assert_eq!(messages(GitCollector::new(repo_path, None, None, vec!["*.rs".to_string()]).with_min_files(Some(3))), Vec::<String>::new());
+        assert_eq!(messages(GitCollector::new(repo_path, None, None, vec!["*.rs".to_string()]).with_min_files(Some(2))), vec!["three files"]);
+    }
+    
+    #[test]
+    fn test_include_empty() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.rs", "one", "code");
+        commit_file(repo.path(), "notes.txt", "two", "docs");
+        git(repo.path(), &["commit", "-q", "--allow-empty", "-m", "empty"]);
+        let repo_path = repo.path().to_str().unwrap();
+        let patterns = vec!["*.rs".to_string()];
+        
+        let unfiltered = GitCollector::new(repo_path, None, None, Vec::new()).collect_history().unwrap();
+        assert_eq!(unfiltered.len(), 3);
+        
+        let dropped = GitCollector::new(repo_path, None, None, patterns.clone()).collect_history().unwrap();
+        assert_eq!(dropped.len(), 1);
+        
+        let kept = GitCollector::new(repo_path, None, None, patterns)
+            .with_include_empty(true)
+            .collect_history()
+            .unwrap();
+        assert_eq!(kept.len(), 3);
+        assert!(kept[0].files.is_empty() && kept[1].files.is_empty());
+        assert_eq!(kept[2].files[0].filename, "a.rs");
+    }
+    
+    #[test]
+    fn test_strip_path_prefix() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "services/api/main.rs", "one", "api");
+        fs::write(repo.path().join("services/api-gateway.rs"), "two").unwrap();
+        commit_file(repo.path(), "README.md", "three", "gateway and readme");
+        
+        let commits = GitCollector::new(repo.path().to_str().unwrap(), None, None, vec!["services/api/".to_string(), "*.md".to_string()])
+            .with_strip_path_prefix(Some("services/api/".to_string()))
+            .collect_history()
+            .unwrap();
+        
+        assert_eq!(commits[0].files[0].filename, "README.md");
+        assert_eq!(commits[1].files[0].filename, "main.rs");
+        
+        let collector = GitCollector::new(".", None, None, Vec::new())
+            .with_strip_path_prefix(Some("services/api".to_string()));
+        assert_eq!(collector.strip_prefix("services/api/main.rs"), "main.rs");
+        assert_eq!(collector.strip_prefix("services/api-gateway.rs"), "services/api-gateway.rs");
+    }
+    
+    #[test]
+    fn test_detect_whitespace_only() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.rs", "fn a() {\n    1\n}\n", "add");
+        commit_file(repo.path(), "a.rs", "fn a() {\n\t1   \n}\n", "reindent");
+        commit_file(repo.path(), "a.rs", "fn a() {\n\t2\n}\n", "edit");
+        fs::write(repo.path().join("a.rs"), "fn a()  {\n\t2\n}\n").unwrap();
+        commit_file(repo.path(), "b.rs", "fn b() {}\n", "mixed");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let flags = |collector: GitCollector| collector.collect_history().unwrap()
+            .iter()
+            .map(|commit| commit.is_whitespace_only)
+            .collect::<Vec<_>>();
+        
+        assert_eq!(
+            flags(GitCollector::new(repo_path, None, None, Vec::new()).with_detect_whitespace_only(true)),
+            vec![false, false, true, false]
+        );
+        assert_eq!(flags(GitCollector::new(repo_path, None, None, Vec::new())), vec![false; 4]);
+        
+        git(repo.path(), &["mv", "b.rs", "c.rs"]);
+        git(repo.path(), &["commit", "-q", "-m", "rename"]);
+        fs::write(repo.path().join("a.rs"), "fn a() {\n    2\n}\n").unwrap();
+        commit_file(repo.path(), "notes.txt", "real change\n", "reindent with notes");
+        let rs_only = || GitCollector::new(repo_path, None, None, vec!["*.rs".to_string()]).with_detect_whitespace_only(true);
+        assert_eq!(flags(rs_only())[..2], [true, false]);
+        let all_files = GitCollector::new(repo_path, None, None, Vec::new()).with_detect_whitespace_only(true);
+        assert_eq!(flags(all_files)[..2], [false, false]);
+    }
+    
+    #[test]
+    fn test_numstat_new_path() {
+        assert_eq!(numstat_new_path("a.rs"), "a.rs");
+        assert_eq!(numstat_new_path("old.rs => new.rs"), "new.rs");
+        assert_eq!(numstat_new_path("src/{old => new}/lib.rs"), "src/new/lib.rs");
+        assert_eq!(numstat_new_path("src/{ => nested}/lib.rs"), "src/nested/lib.rs");
+        assert_eq!(numstat_new_path("src/{nested => }/lib.rs"), "src/lib.rs");
+    }
+    
+    #[test]
+    fn test_parse_trailers() {
+        let body = "Explain the change.\n\n\
+                    Fixes: #42\n\
+                    Reviewed-by: Alice <alice@example.com>\n\
+                    Co-authored-by: Bob <bob@example.com>\n\
+                    Co-authored-by: Carol\n  <carol@example.com>\n\
+                    X-Custom-Ticket : OPS-7";
+        let trailers = parse_trailers(body);
+        assert_eq!(trailers.len(), 4);
+        assert_eq!(trailers["Fixes"], vec!["#42"]);
+        assert_eq!(trailers["Reviewed-by"], vec!["Alice <alice@example.com>"]);
+        assert_eq!(trailers["Co-authored-by"], vec!["Bob <bob@example.com>", "Carol <carol@example.com>"]);
+        assert_eq!(trailers["X-Custom-Ticket"], vec!["OPS-7"]);
+        
+        // Trailers must be in the last paragraph, and a mixed paragraph needs a git trailer.
+        assert!(parse_trailers("Fixes: #1\n\nJust prose at the end.").is_empty());
+        assert!(parse_trailers("Note: this is\nan ordinary paragraph\nof text").is_empty());
+        let mixed = parse_trailers("Some notes\nmore notes\nSigned-off-by: Dana <dana@example.com>\nAcked-by: Eve");
+        assert_eq!(mixed["Signed-off-by"], vec!["Dana <dana@example.com>"]);
+        assert_eq!(mixed["Acked-by"], vec!["Eve"]);
+        assert!(parse_trailers("Has spaces in key: value").is_empty());
+        assert!(parse_trailers("").is_empty());
+    }
+    
+    #[test]
+    fn test_is_test_file() {
+        let defaults = |filename: &str| GitCollector::is_test_file(filename, None);
+        assert!(defaults("tests/integration.rs"));
+        assert!(defaults("crates/core/tests/parse.rs"));
+        assert!(defaults("pkg/server/handler_test.go"));
+        assert!(defaults("web/src/app.spec.ts"));
+        assert!(defaults("test_models.py"));
+        assert!(!defaults("src/testing.rs"));
+        assert!(!defaults("src/contest/main.rs"));
+        
+        let custom = vec!["spec/**".to_string(), "*Test.java".to_string()];
+        assert!(GitCollector::is_test_file("spec/models/user_spec.rb", Some(&custom)));
+        assert!(GitCollector::is_test_file("src/main/FooTest.java", Some(&custom)));
+        assert!(!GitCollector::is_test_file("tests/lib.rs", Some(&custom)));
+    }
+    
+    #[test]
+    fn test_is_code_change() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "src/lib.rs", "fn lib() {}", "code");
+        fs::write(repo.path().join("README.md"), "docs").unwrap();
+        commit_file(repo.path(), "ci.yml", "on: push", "docs and config");
+        commit_file(repo.path(), "Build.GRADLE", "plugins {}", "gradle");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let flags = |collector: GitCollector| collector.collect_history().unwrap()
+            .iter()
+            .map(|commit| commit.is_code_change)
+            .collect::<Vec<_>>();
+        
+        assert_eq!(flags(GitCollector::new(repo_path, None, None, Vec::new())), vec![false, false, true]);
+        assert_eq!(
+            flags(GitCollector::new(repo_path, None, None, Vec::new())
+                .with_code_extensions(Some(vec![".gradle".to_string(), "yml".to_string()]))),
+            vec![true, true, false]
+        );
+    }
+    
+    #[test]
+    fn test_file_status_counts() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "keep.txt", "keep", "first");
+        fs::write(repo.path().join("gone.txt"), "gone").unwrap();
+        fs::write(repo.path().join("move.txt"), "some content that survives the move").unwrap();
+        commit_file(repo.path(), "keep.txt", "keep", "second");
+        
+        fs::remove_file(repo.path().join("gone.txt")).unwrap();
+        fs::rename(repo.path().join("move.txt"), repo.path().join("moved.txt")).unwrap();
+        fs::write(repo.path().join("new.txt"), "new").unwrap();
+        commit_file(repo.path(), "keep.txt", "changed", "third");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let commits = collector.collect_history().unwrap();
+        let latest = &commits[0];
+        
+        assert_eq!(latest.files_added, 1);
+        assert_eq!(latest.files_modified, 1);
+        assert_eq!(latest.files_deleted, 1);
+        assert_eq!(latest.files_renamed, 1);
+    }
+    
+    #[test]
+    fn test_collect_history_parses_files() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "src/lib.rs", "fn main() {}", "add lib");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let commits = collector.collect_history().unwrap();
+        
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "add lib");
+        assert_eq!(commits[0].author_email, "test@example.com");
+        assert_eq!(commits[0].files.len(), 1);
+        assert_eq!(commits[0].files[0].filename, "src/lib.rs");
+        assert_eq!(commits[0].files[0].status, "A");
+    }
+    
+    #[test]
+    fn test_current_branch_and_head_sha() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        git(repo.path(), &["checkout", "-q", "-b", "feature"]);
+        commit_file(repo.path(), "a.txt", "two", "second");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let sha = collector.head_sha().unwrap();
+        
+        assert_eq!(sha.len(), 40);
+        assert_eq!(collector.current_branch().unwrap(), Some("feature".to_string()));
+        
+        git(repo.path(), &["checkout", "-q", "--detach"]);
+        assert_eq!(collector.current_branch().unwrap(), None);
+        assert_eq!(collector.head_sha().unwrap(), sha);
+    }
+    
+    #[test]
+    fn test_short_hash_abbrev_length() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_abbrev_length(Some(12));
+        let commits = collector.collect_history().unwrap();
+        
+        assert_eq!(commits[0].short_hash.len(), 12);
+        assert!(commits[0].hash.starts_with(&commits[0].short_hash));
+    }
+    
+    #[test]
+    fn test_mailmap() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        fs::write(repo.path().join(".mailmap"), "Canonical <canonical@example.com> <test@example.com>\n").unwrap();
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let commits = GitCollector::new(repo_path, None, None, Vec::new())
+            .collect_history()
+            .unwrap();
+        assert_eq!(commits[0].author, "Canonical");
+        assert_eq!(commits[0].author_email, "canonical@example.com");
+        
+        let commits = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_use_mailmap(Some(false))
+            .collect_history()
+            .unwrap();
+        assert_eq!(commits[0].author, "Test");
+        assert_eq!(commits[0].author_email, "test@example.com");
+    }
+    
+    #[test]
+    fn test_revert_detection() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        commit_file(repo.path(), "a.txt", "two", "second");
+        git(repo.path(), &["revert", "--no-edit", "HEAD"]);
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let commits = collector.collect_history().unwrap();
+        
+        assert_eq!(commits.len(), 3);
+        assert_eq!(commits[0].reverted_hash.as_deref(), Some(commits[1].hash.as_str()));
+        assert!(commits[0].body.starts_with("This reverts commit"));
+        assert_eq!(commits[1].reverted_hash, None);
+        assert_eq!(commits[0].tree_hash, commits[2].tree_hash);
+        assert_ne!(commits[0].tree_hash, commits[1].tree_hash);
+    }
+    
+    #[test]
+    fn test_get_current_changes_modes() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "staged.txt", "one\n", "add staged");
+        commit_file(repo.path(), "unstaged.txt", "one\n", "add unstaged");
+        
+        fs::write(repo.path().join("staged.txt"), "one\ntwo\nthree\n").unwrap();
+        git(repo.path(), &["add", "staged.txt"]);
+        fs::write(repo.path().join("unstaged.txt"), "uno\n").unwrap();
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        
+        let unstaged = collector.get_current_changes(DiffMode::Unstaged).unwrap();
+        assert_eq!(unstaged.keys().collect::<Vec<_>>(), vec!["unstaged.txt"]);
+        assert_eq!((unstaged["unstaged.txt"]["additions"], unstaged["unstaged.txt"]["deletions"]), (1, 1));
+        
+        let staged = collector.get_current_changes(DiffMode::Staged).unwrap();
+        assert_eq!(staged.keys().collect::<Vec<_>>(), vec!["staged.txt"]);
+        assert_eq!(staged["staged.txt"]["additions"], 2);
+        assert_eq!(staged["staged.txt"]["deletions"], 0);
+        
+        let both = collector.get_current_changes(DiffMode::Both).unwrap();
+        assert_eq!(both.len(), 2);
+        assert_eq!(both["unstaged.txt"]["total"], 2);
+        
+        let filtered = GitCollector::new(repo.path().to_str().unwrap(), None, None, vec!["staged.txt".to_string()])
+            .get_current_changes(DiffMode::Both)
+            .unwrap();
+        assert_eq!(filtered.keys().collect::<Vec<_>>(), vec!["staged.txt"]);
+        
+        // `--stat` would shorten this path to ".../file.rs" and scale its counts to the graph.
+        let long_path = format!("{}/file.rs", "nested_directory_name".repeat(6));
+        let long_content: String = (0..200).map(|n| format!("{}\n", n)).collect();
+        commit_file(repo.path(), &long_path, "0\n", "add long path");
+        fs::write(repo.path().join(&long_path), long_content).unwrap();
+        let long = GitCollector::new(repo.path().to_str().unwrap(), None, None, vec!["**/*.rs".to_string()])
+            .get_current_changes(DiffMode::Unstaged)
+            .unwrap();
+        assert_eq!(long.keys().collect::<Vec<_>>(), vec![&long_path]);
+        assert_eq!((long[&long_path]["additions"], long[&long_path]["deletions"]), (199, 0));
+    }
+    
+    #[test]
+    fn test_working_tree_diff() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.rs", "1\n2\n", "first");
+        git(repo.path(), &["tag", "v1"]);
+        commit_file(repo.path(), "a.rs", "1\n2\n3\n", "second");
+        fs::write(repo.path().join("a.rs"), "1\n3\n4\n").unwrap();
+        fs::write(repo.path().join("notes.txt"), "ignored\n").unwrap();
+        git(repo.path(), &["add", "notes.txt"]);
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, vec!["*.rs".to_string()]);
+        let changes = collector.working_tree_diff("v1").unwrap();
+        
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes["a.rs"]["additions"], 2);
+        assert_eq!(changes["a.rs"]["deletions"], 1);
+        
+        let err = collector.working_tree_diff("no-such-ref").unwrap_err();
+        assert!(err.to_string().contains("Unknown ref"));
+    }
+    
+    #[test]
+    fn test_get_commit() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one\n", "first");
+        commit_file(repo.path(), "b.txt", "two\n", "second");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let first_hash = collector.collect_history().unwrap()[1].hash.clone();
+        
+        let commit = collector.get_commit(&first_hash[..10]).unwrap();
+        assert_eq!(commit.hash, first_hash);
+        assert_eq!(commit.message, "first");
+        assert_eq!(commit.files.len(), 1);
+        assert_eq!(commit.files[0].filename, "a.txt");
+        
+        let err = collector.get_commit("0123456789abcdef").unwrap_err();
+        assert!(err.to_string().contains("Unknown commit"));
+    }
+    
+    #[test]
+    fn test_get_commits() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        commit_file(repo.path(), "b.txt", "two", "second");
+        commit_file(repo.path(), "c.txt", "three", "third");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_record_commands(true);
+        let all = collector.collect_history().unwrap();
+        let tree = all[0].tree_hash.clone();
+        
+        let requested = vec![all[2].hash.clone(), "deadbeef".to_string(), all[0].short_hash.clone(), tree];
+        let commits = collector.get_commits(&requested).unwrap();
+        
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].message, "first");
+        assert_eq!(commits[1].message, "third");
+        assert_eq!(commits[1].hash, all[0].hash);
+        assert_eq!(commits[1].files[0].filename, "c.txt");
+        assert_eq!(commits[1].index, 1);
+        assert!(collector.last_command().unwrap().contains("--stdin"));
+        
+        assert!(collector.get_commits(&["deadbeef".to_string()]).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_collect_repositories() {
+        let first = init_test_repo();
+        commit_file(first.path(), "a.txt", "one\n", "first repo");
+        let second = init_test_repo();
+        commit_file(second.path(), "b.txt", "one\n", "second repo");
+        commit_file(second.path(), "b.txt", "two\n", "second repo again");
+        let missing = first.path().join("missing").to_str().unwrap().to_string();
+        
+        let repo_paths = vec![
+            first.path().to_str().unwrap().to_string(),
+            second.path().to_str().unwrap().to_string(),
+            missing,
+        ];
+        
+        let results = collect_repositories(&repo_paths, 2, |repo_path| {
+            GitCollector::new(repo_path, None, None, Vec::new())
+        }).unwrap();
+        
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, repo_paths[0]);
+        assert_eq!(results[0].1.as_ref().unwrap().len(), 1);
+        assert_eq!(results[1].1.as_ref().unwrap().len(), 2);
+        assert!(results[2].1.is_err());
+    }
+    
+    #[test]
+    fn test_git_command_environment() {
+        let collector = GitCollector::new(".", None, None, Vec::new());
+        let cmd = collector.git_command(&["log"]);
+        
+        let args: Vec<String> = cmd.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+        assert_eq!(args.first().map(String::as_str), Some("--no-pager"));
+        assert_eq!(args.last().map(String::as_str), Some("log"));
+        assert!(args.windows(2).any(|pair| pair == ["-c", "log.showSignature=false"]));
+        
+        let envs: HashMap<String, Option<String>> = cmd.get_envs()
+            .map(|(key, value)| (key.to_string_lossy().to_string(), value.map(|v| v.to_string_lossy().to_string())))
+            .collect();
+        assert_eq!(envs.get("GIT_TERMINAL_PROMPT"), Some(&Some("0".to_string())));
+        assert_eq!(envs.get("GIT_TRACE"), Some(&None));
+        assert_eq!(envs.get("GIT_PAGER"), Some(&None));
+    }
+    
+    #[test]
+    fn test_map_repositories_current_changes() {
+        let first = init_test_repo();
+        commit_file(first.path(), "a.txt", "one\n", "first repo");
+        fs::write(first.path().join("a.txt"), "one\ntwo\n").unwrap();
+        let second = init_test_repo();
+        commit_file(second.path(), "b.txt", "one\n", "second repo");
+        
+        let repo_paths = vec![
+            first.path().to_str().unwrap().to_string(),
+            second.path().to_str().unwrap().to_string(),
+        ];
+        
+        let results = map_repositories(
+            &repo_paths,
+            2,
+            |repo_path| GitCollector::new(repo_path, None, None, Vec::new()),
+            |collector| collector.get_current_changes(DiffMode::Both)
+        ).unwrap();
+        
+        let first_changes = results[0].1.as_ref().unwrap();
+        assert_eq!(first_changes["a.txt"]["additions"], 1);
+        assert!(results[1].1.as_ref().unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_record_commands() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one\n", "first");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let silent = GitCollector::new(repo_path, None, None, Vec::new());
+        silent.head_sha().unwrap();
+        assert_eq!(silent.last_command(), None);
+        
+        let recording = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_record_commands(true);
+        recording.head_sha().unwrap();
+        assert_eq!(recording.last_command().as_deref(), Some("git --no-pager rev-parse HEAD"));
+        
+        let err = recording.run_git_command(&["rev-parse", "--verify", "no-such-ref"]).unwrap_err();
+        assert!(err.to_string().contains("git --no-pager rev-parse --verify no-such-ref"));
+    }
+    
+    #[test]
+    fn test_line_counts() {
+        let repo = init_test_repo();
+        fs::write(repo.path().join("image.bin"), [0u8, 159, 146, 150, 0, 10]).unwrap();
+        fs::write(repo.path().join("notes.md"), "no trailing newline").unwrap();
+        commit_file(repo.path(), "src/lib.rs", "one\ntwo\nthree\n", "add files");
+        fs::write(repo.path().join("src/lib.rs"), "uncommitted\n").unwrap();
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let counts = collector.line_counts().unwrap();
+        let find = |name: &str| counts.iter().find(|count| count.filename == name).unwrap();
+        
+        assert_eq!(counts.len(), 3);
+        assert_eq!(find("src/lib.rs").lines, 3);
+        assert_eq!(find("notes.md").lines, 1);
+        assert!(find("image.bin").binary);
+        assert!(!find("src/lib.rs").binary);
+        
+        let filtered = GitCollector::new(repo.path().to_str().unwrap(), None, None, vec!["*.md".to_string()]);
+        assert_eq!(filtered.list_tracked_files().unwrap(), vec!["notes.md"]);
+    }
+    
+    #[test]
+    fn test_ref_name_with_date_range() {
+        let repo = init_test_repo();
+        let commit_at = |message: &str, date: &str| {
+            fs::write(repo.path().join(format!("{}.txt", message)), message).unwrap();
+            git(repo.path(), &["add", "-A"]);
+            let status = Command::new("git")
+                .current_dir(repo.path())
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com", "commit", "-q", "-m", message])
+                .env("GIT_AUTHOR_DATE", date)
+                .env("GIT_COMMITTER_DATE", date)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        commit_at("main-1", "2020-01-01T00:00:00Z");
+        commit_at("main-2", "2020-02-01T00:00:00Z");
+        git(repo.path(), &["checkout", "-q", "-b", "feature"]);
+        commit_at("feature-1", "2020-03-01T00:00:00Z");
+        commit_at("feature-2", "2020-04-01T00:00:00Z");
+        commit_at("feature-3", "2020-05-01T00:00:00Z");
+        git(repo.path(), &["checkout", "-q", "-"]);
+        commit_at("main-3", "2020-03-15T00:00:00Z");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let collector = || GitCollector::new(repo_path, None, None, Vec::new())
+            .with_ref_name(Some("feature".to_string()))
+            .with_since_date(Some("2020-01-15".to_string()))
+            .with_until_date(Some("2020-04-15".to_string()));
+        let messages = |collector: GitCollector| collector.collect_history().unwrap()
+            .into_iter()
+            .map(|commit| commit.message)
+            .collect::<Vec<_>>();
+        
+        assert_eq!(messages(collector()), vec!["feature-2", "feature-1", "main-2"]);
+        assert_eq!(messages(collector().with_ref_name(None)), vec!["main-3", "main-2"]);
+        
+        let plan = collector().plan().unwrap();
+        assert!(plan.contains(&"--since=2020-01-15".to_string()) && plan.contains(&"--until=2020-04-15".to_string()));
+        assert_eq!(plan.last().map(String::as_str), Some("feature"));
+        
+        assert_ne!(collector().get_cache_key().unwrap(), collector().with_ref_name(None).get_cache_key().unwrap());
+        
+        let err = collector().with_ref_name(Some("missing".to_string())).collect_history().unwrap_err();
+        assert!(err.to_string().contains("Unknown ref"), "{}", err);
+        assert!(collector().with_ref_name(Some("--all".to_string())).plan().is_err());
+        let conflicting = GitCollector::new(repo_path, None, Some(30), Vec::new())
+            .with_since_date(Some("2020-01-15".to_string()));
+        assert!(conflicting.plan().is_err());
+    }
+    
+    #[test]
+    fn test_ascending() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        commit_file(repo.path(), "a.txt", "two", "second");
+        commit_file(repo.path(), "a.txt", "three", "third");
+        let repo_path = repo.path().to_str().unwrap();
+        let summary = |commits: Vec<Commit>| commits.into_iter()
+            .map(|commit| (commit.index, commit.message))
+            .collect::<Vec<_>>();
+        
+        let commits = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_ascending(true)
+            .collect_history()
+            .unwrap();
+        assert_eq!(summary(commits), vec![
+            (0, "first".to_string()),
+            (1, "second".to_string()),
+            (2, "third".to_string()),
+        ]);
+        
+        let newest_two = GitCollector::new(repo_path, Some(2), None, Vec::new())
+            .with_ascending(true)
+            .stream_commits()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(summary(newest_two), vec![(0, "second".to_string()), (1, "third".to_string())]);
+    }
+    
+    #[test]
+    fn test_since_last_tag() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one\n", "first");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let collector = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_since_last_tag(true);
+        assert_eq!(collector.last_tag().unwrap(), None);
+        assert_eq!(collector.collect_history().unwrap().len(), 1);
+        
+        git(repo.path(), &["tag", "v1.0"]);
+        commit_file(repo.path(), "a.txt", "two\n", "second");
+        commit_file(repo.path(), "a.txt", "three\n", "third");
+        
+        let collector = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_since_last_tag(true);
+        assert_eq!(collector.last_tag().unwrap().as_deref(), Some("v1.0"));
+        
+        let commits = collector.collect_history().unwrap();
+        let messages: Vec<&str> = commits.iter().map(|commit| commit.message.as_str()).collect();
+        assert_eq!(messages, vec!["third", "second"]);
+    }
+    
+    #[test]
+    fn test_include_merge_diffs() {
+        let repo = init_test_repo();
+        git(repo.path(), &["checkout", "-q", "-b", "main"]);
+        commit_file(repo.path(), "f.txt", "1\n2\n3\n", "base");
+        git(repo.path(), &["checkout", "-q", "-b", "side"]);
+        commit_file(repo.path(), "f.txt", "1\nside\n3\n", "side");
+        git(repo.path(), &["checkout", "-q", "main"]);
+        commit_file(repo.path(), "f.txt", "1\nmain\n3\n", "main");
+        let merge = Command::new("git")
+            .current_dir(repo.path())
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com", "merge", "-q", "side"])
+            .output()
+            .unwrap();
+        assert!(!merge.status.success(), "merge should stop on the conflict");
+        commit_file(repo.path(), "f.txt", "1\nresolved\nextra\n3\n", "merge side");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let without = GitCollector::new(repo_path, None, None, Vec::new())
+            .collect_history()
+            .unwrap();
+        assert_eq!(without[0].message, "merge side");
+        assert!(without[0].files.is_empty());
+        assert_eq!(without[0].parents.len(), 2);
+        assert_eq!(without[1].parents.len(), 1);
+        assert!(!without[0].is_root && !without[1].is_root);
+        assert!(without.last().unwrap().is_root);
+        
+        let with = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_include_merge_diffs(true)
+            .collect_history()
+            .unwrap();
+        let merge_file = &with[0].files[0];
+        assert_eq!(merge_file.filename, "f.txt");
+        assert!(merge_file.from_merge);
+        assert_eq!((merge_file.additions, merge_file.deletions), (2, 2));
+        assert!(with[1..].iter().flat_map(|commit| &commit.files).all(|file| !file.from_merge));
+    }
+    
+    #[test]
+    fn test_include_merge_diffs_with_clean_file() {
+        let repo = init_test_repo();
+        git(repo.path(), &["checkout", "-q", "-b", "main"]);
+        commit_file(repo.path(), "z.txt", "1\n2\n3\n", "base");
+        git(repo.path(), &["checkout", "-q", "-b", "side"]);
+        commit_file(repo.path(), "a.txt", "clean\n", "side clean");
+        commit_file(repo.path(), "z.txt", "1\nside\n3\n", "side conflict");
+        git(repo.path(), &["checkout", "-q", "main"]);
+        commit_file(repo.path(), "z.txt", "1\nmain\n3\n", "main");
+        let merge = Command::new("git")
+            .current_dir(repo.path())
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com", "merge", "-q", "side"])
+            .output()
+            .unwrap();
+        assert!(!merge.status.success(), "merge should stop on the conflict");
+        commit_file(repo.path(), "z.txt", "1\nresolved\nextra\nmore\n3\n", "merge side");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_include_merge_diffs(true);
+        let merge_files = |commit: &Commit| -> Vec<(String, u32, u32, bool)> {
+            commit.files.iter()
+                .map(|file| (file.filename.clone(), file.additions, file.deletions, file.from_merge))
+                .collect()
+        };
+        let commits = collector.collect_history().unwrap();
+        assert_eq!(merge_files(&commits[0]), vec![("z.txt".to_string(), 3, 2, true)]);
+        assert!(!commits[0].files[0].binary);
+        
+        // Looking the merge up directly must report the same files as the walk.
+        assert_eq!(merge_files(&collector.get_commit("HEAD").unwrap()), merge_files(&commits[0]));
+        assert_eq!(merge_files(&collector.get_commits(&["HEAD".to_string()]).unwrap()[0]), merge_files(&commits[0]));
+    }
+    
+    #[test]
+    fn test_parse_combined_diff() {
+        let output = "diff --cc z.txt\nindex 3e99f1c,cd3b0e3..18c2d9a\n--- a/z.txt\n+++ b/z.txt\n\
+            @@@ -1,3 -1,3 +1,4 @@@\n  1\n- main\n -side\n++resolved\n+ from side\n  3\n\
+            diff --cc logo.png\nindex 1111111,2222222..3333333\nBinary files differ\n";
+        let counts = parse_combined_diff(output);
+        assert_eq!(counts["z.txt"], Some((1, 2)));
+        assert_eq!(counts["logo.png"], None);
+    }
+    
+    #[test]
+    fn test_max_output_bytes() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one\n", "first");
+        commit_file(repo.path(), "a.txt", "two\n", "second");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let capped = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_max_output_bytes(Some(16));
+        let err = capped.collect_history().unwrap_err();
+        assert!(matches!(err, GitMetricsError::CommandError(_)));
+        assert!(err.to_string().contains("16 byte limit"));
+        
+        let roomy = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_max_output_bytes(Some(1024 * 1024));
+        assert_eq!(roomy.collect_history().unwrap().len(), 2);
+    }
+    
+    #[test]
+    fn test_get_cache_key() {
+        let dir = tempdir().unwrap();
+        let repo_path = dir.path().to_str().unwrap();
+        
+        let collector1 = GitCollector::new(repo_path, Some(10), None, Vec::new());
+        let collector2 = GitCollector::new(repo_path, Some(10), None, Vec::new());
+        let collector3 = GitCollector::new(repo_path, Some(20), None, Vec::new());
+        
+        let key1 = collector1.get_cache_key().unwrap();
+        let key2 = collector2.get_cache_key().unwrap();
+        let key3 = collector3.get_cache_key().unwrap();
+        
+        assert_eq!(key1, key2);
+        assert_ne!(key1, key3);
+        
+        let collector4 = GitCollector::new(repo_path, Some(10), None, Vec::new())
+            .with_extra_log_args(vec!["--first-parent".to_string()]);
+        assert_ne!(key1, collector4.get_cache_key().unwrap());
+    }
+    
+    #[test]
+    fn test_cache_key_covers_every_output_option() {
+        let repo = init_test_repo();
+        let repo_path = repo.path().to_str().unwrap();
+        let other = init_test_repo();
+        let base = || GitCollector::new(repo_path, None, None, Vec::new());
+        let base_key = base().get_cache_key().unwrap();
+        
+        let variants = vec![
+            ("repo", GitCollector::new(other.path().to_str().unwrap(), None, None, Vec::new())),
+            ("max_commits", GitCollector::new(repo_path, Some(5), None, Vec::new())),
+            ("since_days", GitCollector::new(repo_path, None, Some(7), Vec::new())),
+            ("file_patterns", GitCollector::new(repo_path, None, None, vec!["*.rs".to_string()])),
+            ("extra_log_args", base().with_extra_log_args(vec!["--no-merges".to_string()])),
+            ("abbrev_length", base().with_abbrev_length(Some(12))),
+            ("use_mailmap", base().with_use_mailmap(Some(true))),
+            ("normalize_rename_churn", base().with_normalize_rename_churn(true)),
+            ("since_last_tag", base().with_since_last_tag(true)),
+            ("include_merge_diffs", base().with_include_merge_diffs(true)),
+            ("detect_renames", base().with_detect_renames(Some(false))),
+            ("detect_copies", base().with_detect_copies(true)),
+            ("rename_threshold", base().with_rename_threshold(Some(50))),
+            ("status_filter", base().with_status_filter(Some(vec!['A']))),
+            ("date_format", base().with_date_format(Some("short".to_string()))),
+            ("min_files", base().with_min_files(Some(2))),
+            ("include_empty", base().with_include_empty(true)),
+            ("strip_path_prefix", base().with_strip_path_prefix(Some("src".to_string()))),
+            ("code_extensions", base().with_code_extensions(Some(vec!["rs".to_string()]))),
+            ("traversal_order", base().with_traversal_order(Some(TraversalOrder::Topo))),
+            ("author_aliases", base().with_author_aliases(HashMap::from([("a@x".to_string(), "b@x".to_string())]))),
+            ("pathspecs", base().with_pathspecs(vec![":(exclude)vendor".to_string()])),
+            ("capture_signatures", base().with_capture_signatures(true)),
+            ("git_dir", base().with_git_dir(Some(".git".to_string()))),
+            ("max_file_lines", base().with_max_file_lines(Some(1000))),
+            ("follow_path", base().with_follow_path(Some("a.txt".to_string()))),
+            ("detect_whitespace_only", base().with_detect_whitespace_only(true)),
+            ("ref_name", base().with_ref_name(Some("main".to_string()))),
+            ("since_date", base().with_since_date(Some("2020-01-01".to_string()))),
+            ("until_date", base().with_until_date(Some("2020-01-01".to_string()))),
+            ("ascending", base().with_ascending(true)),
+            ("extensions", base().with_extensions(vec!["rs".to_string()])),
+            ("exclude_paths", base().with_exclude_paths(vec!["vendor/".to_string()])),
+            ("exclude_authors", base().with_exclude_authors(vec!["bot".to_string()])),
+            ("author_email", base().with_author_email(Some("a@x".to_string()))),
+        ];
+        
+        for (option, collector) in variants {
+            assert_ne!(collector.get_cache_key().unwrap(), base_key, "{} is not part of the cache key", option);
+        }
+        
+        let unkeyed = vec![
+            base().with_fail_on_shallow(true),
+            base().with_max_commits_per_author(Some(1)),
+            base().with_record_commands(true),
+            base().with_max_output_bytes(Some(1024)),
+        ];
+        for collector in unkeyed {
+            assert_eq!(collector.get_cache_key().unwrap(), base_key);
         }
         
-        false
+        let split = GitCollector::new(repo_path, None, None, vec!["a,b".to_string()]);
+        let joined = GitCollector::new(repo_path, None, None, vec!["a".to_string(), "b".to_string()]);
+        assert_ne!(split.get_cache_key().unwrap(), joined.get_cache_key().unwrap());
     }
     
-    fn parse_single_commit(&self, commit_data: &str) -> Result<Commit> {
-        let lines: Vec<&str> = commit_data.lines().collect();
+    #[test]
+    fn test_raw_log() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        commit_file(repo.path(), "b.txt", "two", "second");
         
-        let end_index = lines.iter()
-            .position(|&line| line == COMMIT_END_MARKER)
-            .ok_or_else(|| GitMetricsError::Other(
-                "Malformed commit data: no COMMIT_END marker".to_string()
-            ))?;
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), Some(1), None, Vec::new());
+        let raw = String::from_utf8(collector.raw_log().unwrap()).unwrap();
+        let fields: Vec<&str> = raw.split('\0').collect();
         
-        if lines.len() < 4 {
-            return Err(GitMetricsError::Other(
-                "Not enough lines in commit data".to_string()
-            ));
-        }
+        assert_eq!(fields[0].len(), 40);
+        assert_eq!(fields[2], "Test");
+        assert_eq!(fields[6], "second");
+        assert!(fields.contains(&"b.txt"));
+        assert!(!fields.contains(&"a.txt"));
+    }
+    
+    #[test]
+    fn test_submodule_path() {
+        let sub = init_test_repo();
+        commit_file(sub.path(), "lib.rs", "fn lib() {}", "sub commit");
         
-        let commit_hash = lines[0].to_string();
-        let author = lines[1].to_string();
-        let date = lines[2].to_string();
-        let message = lines[3].to_string();
+        let repo = init_test_repo();
+        commit_file(repo.path(), "main.rs", "fn main() {}", "super commit");
+        git(repo.path(), &[
+            "-c", "protocol.file.allow=always",
+            "submodule", "add", "-q", sub.path().to_str().unwrap(), "vendor/lib",
+        ]);
+        git(repo.path(), &["commit", "-q", "-m", "add submodule"]);
         
-        let files = self.parse_file_changes(&lines[4..end_index])?;
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let path = collector.submodule_path("vendor/lib").unwrap();
+        assert_eq!(path, repo.path().join("vendor/lib"));
         
-        Ok(Commit {
-            hash: commit_hash,
-            author,
-            date,
-            message,
-            files,
-        })
+        let commits = GitCollector::new(path.to_str().unwrap(), None, None, Vec::new())
+            .collect_history()
+            .unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "sub commit");
+        
+        assert!(collector.submodule_path("missing").is_err());
+        
+        git(repo.path(), &["submodule", "deinit", "-q", "-f", "vendor/lib"]);
+        let err = collector.submodule_path("vendor/lib").unwrap_err();
+        assert!(err.to_string().contains("not initialized"));
     }
     
-    fn parse_file_changes(&self, file_lines: &[&str]) -> Result<Vec<FileChange>> {
-        let mut files = Vec::new();
+    #[test]
+    fn test_exclude_authors() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "human change");
+        fs::write(repo.path().join("deps.lock"), "v2").unwrap();
+        git(repo.path(), &["add", "-A"]);
+        git(repo.path(), &["commit", "-q", "-m", "bump deps", "--author", "dependabot[bot] <49699333+dependabot[bot]@users.noreply.github.com>"]);
+        fs::write(repo.path().join("deps.lock"), "v3").unwrap();
+        git(repo.path(), &["add", "-A"]);
+        git(repo.path(), &["commit", "-q", "-m", "update deps", "--author", "Renovate Bot <bot@renovateapp.com>"]);
         
-        for line in file_lines {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-            
-            let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() < 2 {
-                continue;
-            }
-            
-            let status_str = parts[0].to_string();
-            let filename = parts[1].to_string();
-            
-            if !self.matches_file_pattern(&filename) {
-                continue;
-            }
-            
-            let (additions, deletions) = self.status_to_change_count(&status_str);
-            
-            files.push(FileChange {
-                filename,
-                status: status_str,
-                additions,
-                deletions,
-            });
-        }
+        let repo_path = repo.path().to_str().unwrap();
+        let try_collect = |exclude: &[&str]| {
+            GitCollector::new(repo_path, None, None, Vec::new())
+                .with_exclude_authors(exclude.iter().map(|author| author.to_string()).collect())
+                .collect_history()
+        };
+        let collect = |exclude: &[&str]| try_collect(exclude).unwrap();
+        
+        assert_eq!(collect(&[]).len(), 3);
+        assert_eq!(collect(&["dependabot[bot]"]).len(), 2);
+        // Without the prefix an entry is never a regex: "[bot]" would otherwise match "t".
+        assert_eq!(collect(&["Tes[bot]"]).len(), 3);
         
-        Ok(files)
+        let humans = collect(&["dependabot[bot]", r"re:@renovate\w*\.com$"]);
+        assert_eq!(humans.len(), 1);
+        assert_eq!(humans[0].message, "human change");
+        
+        assert!(try_collect(&["re:("]).is_err());
     }
     
-    fn status_to_change_count(&self, status: &str) -> (u32, u32) {
-        match status.chars().next() {
-            Some('A') => (1, 0),
-            Some('D') => (0, 1),
-            Some('M') | Some('R') | Some('C') => (1, 1),
-            _ => (0, 0),
-        }
+    #[test]
+    fn test_unmatched_patterns() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "src/lib.rs", "pub fn lib() {}", "code");
+        
+        let patterns = ["*.rss", "src/", "**/*.rs"].iter().map(|pattern| pattern.to_string()).collect();
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, patterns);
+        assert_eq!(collector.unmatched_patterns(), vec!["*.rss", "src/", "**/*.rs"]);
+        
+        assert_eq!(collector.collect_history().unwrap().len(), 1);
+        assert_eq!(collector.unmatched_patterns(), vec!["*.rss"]);
+        
+        // A second collection is served from the cache and must report the same hits.
+        assert_eq!(collector.collect_history().unwrap().len(), 1);
+        assert_eq!(collector.unmatched_patterns(), vec!["*.rss"]);
+        
+        fs::write(repo.path().join("notes.rss"), "feed").unwrap();
+        git(repo.path(), &["add", "notes.rss"]);
+        collector.get_current_changes(DiffMode::Staged).unwrap();
+        collector.list_tracked_files().unwrap();
+        assert_eq!(collector.unmatched_patterns(), vec!["*.rss"]);
+        
+        let prefixed = GitCollector::new(repo.path().to_str().unwrap(), None, None, vec!["src/*.rs".to_string()])
+            .with_strip_path_prefix(Some("src".to_string()));
+        assert_eq!(prefixed.collect_history().unwrap()[0].files[0].filename, "lib.rs");
+        assert!(prefixed.unmatched_patterns().is_empty());
     }
     
-    pub fn get_current_changes(&self) -> Result<HashMap<String, HashMap<String, u32>>> {
-        info!("Analyzing current changes...");
+    #[test]
+    fn test_pathspecs() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "src/main.rs", "fn main() {}", "code");
+        commit_file(repo.path(), "vendor/lib.rs", "pub fn lib() {}", "vendored");
         
-        let diff_output = self.run_git_command(&["diff", "--stat"])?;
-        debug!("Git diff output: {}", diff_output);
+        let collect = |pathspecs: &[&str]| {
+            GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+                .with_pathspecs(pathspecs.iter().map(|pathspec| pathspec.to_string()).collect())
+                .collect_history()
+        };
         
-        let mut changes = HashMap::new();
+        let commits = collect(&[":(exclude)vendor/**"]).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].files[0].filename, "src/main.rs");
         
-        for line in diff_output.lines() {
-            if let Some(file_changes) = self.parse_diff_line(line) {
-                changes.insert(file_changes.0, file_changes.1);
-            }
-        }
+        assert!(collect(&[":(exclude"]).is_err());
+        assert!(collect(&[""]).is_err());
         
-        info!("Analyzed {} changes", changes.len());
-        Ok(changes)
+        let plan = GitCollector::new(".", None, None, Vec::new())
+            .with_since_last_tag(true)
+            .with_pathspecs(vec!["src".to_string()])
+            .plan()
+            .unwrap();
+        assert_eq!(plan[plan.len() - 3..], ["<last-tag>..HEAD", "--", "src"]);
     }
     
-    fn parse_diff_line(&self, line: &str) -> Option<(String, HashMap<String, u32>)> {
-        let line = line.trim();
-        if line.is_empty() || !line.contains('|') {
-            return None;
-        }
+    #[test]
+    fn test_extensions() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "src/main.rs", "fn main() {}", "code");
+        commit_file(repo.path(), "Cargo.toml", "[package]", "manifest");
+        commit_file(repo.path(), "README.md", "readme", "docs");
+        commit_file(repo.path(), "src/lib.rs.orig", "backup", "backup");
+        let repo_path = repo.path().to_str().unwrap();
+        let collector = |extensions: &[&str]| GitCollector::new(repo_path, None, None, Vec::new())
+            .with_extensions(extensions.iter().map(|extension| extension.to_string()).collect());
         
-        let parts: Vec<&str> = line.split('|').collect();
-        if parts.len() != 2 {
-            return None;
-        }
+        let commits = collector(&["rs", ".toml"]).collect_history().unwrap();
+        let files: Vec<&str> = commits.iter().flat_map(|commit| &commit.files).map(|file| file.filename.as_str()).collect();
+        assert_eq!(files, vec!["Cargo.toml", "src/main.rs"]);
         
-        let filename = parts[0].trim().to_string();
+        let commits = collector(&["rs"]).with_pathspecs(vec!["README.md".to_string()]).collect_history().unwrap();
+        let messages: Vec<&str> = commits.iter().map(|commit| commit.message.as_str()).collect();
+        assert_eq!(messages, vec!["code"]);
         
-        if !self.matches_file_pattern(&filename) {
-            return None;
-        }
+        assert_eq!(collector(&["rs"]).list_tracked_files().unwrap(), vec!["src/main.rs"]);
+        
+        let plan = collector(&["rs"]).plan().unwrap();
+        assert_eq!(plan[plan.len() - 2..], ["--", "*.rs"]);
+        assert!(collector(&["*.rs"]).plan().is_err());
+        assert!(collector(&[""]).plan().is_err());
+    }
+    
+    #[test]
+    fn test_exclude_paths() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "src/main.rs", "fn main() {}", "code");
+        commit_file(repo.path(), "vendor/dep/lib.rs", "pub fn dep() {}", "vendored");
+        commit_file(repo.path(), "src/generated/schema.rs", "pub struct Schema;", "generated");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let commits = GitCollector::new(repo_path, None, None, vec!["**/*.rs".to_string()])
+            .with_exclude_paths(vec!["vendor/".to_string(), "src/generated/*.rs".to_string()])
+            .collect_history()
+            .unwrap();
+        let files: Vec<&str> = commits.iter().flat_map(|commit| &commit.files).map(|file| file.filename.as_str()).collect();
+        assert_eq!(files, vec!["src/main.rs"]);
+        
+        let collector = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_exclude_paths(vec!["src/main.rs".to_string()]);
+        assert!(!collector.matches_file_pattern("src/main.rs"));
+        assert!(collector.matches_file_pattern("vendor/dep/lib.rs"));
+    }
+    
+    #[test]
+    fn test_follow_path() {
+        let repo = init_test_repo();
+        let content = "line one\nline two\nline three\nline four\n";
+        commit_file(repo.path(), "old.txt", content, "add old");
+        commit_file(repo.path(), "other.txt", "unrelated", "unrelated");
+        git(repo.path(), &["mv", "old.txt", "new.txt"]);
+        git(repo.path(), &["commit", "-q", "-m", "rename"]);
+        commit_file(repo.path(), "new.txt", &format!("{}line five\n", content), "edit new");
+        let repo_path = repo.path().to_str().unwrap();
+        let collector = || GitCollector::new(repo_path, None, None, Vec::new())
+            .with_follow_path(Some("new.txt".to_string()));
         
-        let stats = parts[1].trim();
-        let (insertions, deletions) = self.parse_diff_stats(stats);
+        let names: Vec<String> = collector().path_names("new.txt").unwrap().into_iter().collect();
+        assert_eq!(names, vec!["new.txt", "old.txt"]);
         
-        let mut file_changes = HashMap::new();
-        file_changes.insert("additions".to_string(), insertions);
-        file_changes.insert("deletions".to_string(), deletions);
-        file_changes.insert("total".to_string(), insertions + deletions);
+        let commits = collector().collect_history().unwrap();
+        let messages: Vec<&str> = commits.iter().map(|commit| commit.message.as_str()).collect();
+        assert_eq!(messages, vec!["edit new", "rename", "add old"]);
+        assert!(commits.iter().flat_map(|commit| &commit.files).all(|file| file.filename != "other.txt"));
         
-        Some((filename, file_changes))
+        assert!(collector().path_names("missing.txt").is_err());
+        assert!(collector().with_pathspecs(vec!["src".to_string()]).collect_history().is_err());
     }
     
-    fn parse_diff_stats(&self, stats: &str) -> (u32, u32) {
-        let mut insertions = 0;
-        let mut deletions = 0;
+    #[test]
+    fn test_author_aliases() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "from work");
+        fs::write(repo.path().join("a.txt"), "two").unwrap();
+        git(repo.path(), &["add", "-A"]);
+        git(repo.path(), &["commit", "-q", "-m", "from home", "--author", "Test <test@home.example>"]);
         
-        if stats.contains("insertion") {
-            if let Some(n) = stats.split_whitespace()
-                .next()
-                .and_then(|s| s.parse::<u32>().ok()) {
-                insertions = n;
-            }
-        }
+        let aliases = HashMap::from([("test@home.example".to_string(), "test@example.com".to_string())]);
+        let commits = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_author_aliases(aliases)
+            .collect_history()
+            .unwrap();
         
-        if stats.contains("deletion") {
-            if let Some(n) = stats.split("deletion")
-                .next()
-                .and_then(|s| s.trim().split_whitespace().next())
-                .and_then(|s| s.parse::<u32>().ok()) {
-                deletions = n;
-            }
-        }
+        assert!(commits.iter().all(|commit| commit.author_email == "test@example.com"));
+        let ownership = analysis::file_ownership(&commits);
+        assert_eq!(ownership[0].contributor_count, 1);
+        assert!(analysis::collaboration_graph(&commits).is_empty());
+    }
+    
+    #[test]
+    fn test_author_first_timestamp() {
+        let repo = init_test_repo();
+        let commit_as = |author: &str, date: &str, content: &str| {
+            fs::write(repo.path().join("a.txt"), content).unwrap();
+            git(repo.path(), &["add", "-A"]);
+            git(repo.path(), &["commit", "-q", "-m", content, "--author", author, "--date", date]);
+        };
+        commit_as("Old <old@example.com>", "2020-01-01T00:00:00Z", "one");
+        commit_as("New <new@laptop.example>", "2021-01-01T00:00:00Z", "two");
+        commit_as("New <new@example.com>", "2022-01-01T00:00:00Z", "three");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), Some(1), None, Vec::new());
+        assert_eq!(collector.author_first_timestamp("old@example.com").unwrap(), Some(1577836800));
+        assert_eq!(collector.author_first_timestamp("NEW@example.com").unwrap(), Some(1640995200));
+        assert_eq!(collector.author_first_timestamp("nobody@example.com").unwrap(), None);
+        
+        let aliased = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_author_aliases(HashMap::from([("new@laptop.example".to_string(), "new@example.com".to_string())]));
+        assert_eq!(aliased.author_first_timestamp("new@example.com").unwrap(), Some(1609459200));
+        
+        git(repo.path(), &["checkout", "-q", "-b", "old-only", "HEAD~2"]);
+        git(repo.path(), &["checkout", "-q", "-"]);
+        let on_ref = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_ref_name(Some("old-only".to_string()));
+        assert_eq!(on_ref.author_first_timestamp("new@example.com").unwrap(), None);
+    }
+    
+    #[test]
+    fn test_author_email() {
+        let repo = init_test_repo();
+        let commit_as = |author: &str, content: &str| {
+            fs::write(repo.path().join("a.txt"), content).unwrap();
+            git(repo.path(), &["add", "-A"]);
+            git(repo.path(), &["commit", "-q", "-m", content, "--author", author]);
+        };
+        commit_as("Dev <dev@laptop.example>", "one");
+        commit_as("Dev <Dev+Git@Example.com>", "two");
+        commit_as("Other <other@example.com>", "three");
+        
+        let collect = |max_commits: Option<u32>| {
+            GitCollector::new(repo.path().to_str().unwrap(), max_commits, None, Vec::new())
+                .with_author_email(Some("dev+git@example.com".to_string()))
+                .with_author_aliases(HashMap::from([("dev@laptop.example".to_string(), "dev+git@example.com".to_string())]))
+                .collect_history()
+                .unwrap()
+        };
+        
+        // The newest commit is by someone else, so git must apply the filter before the cap.
+        let capped = collect(Some(1));
+        assert_eq!(capped.len(), 1);
+        assert_eq!(capped[0].message, "two");
+        
+        let all: Vec<_> = collect(None).into_iter().map(|commit| commit.message).collect();
+        assert_eq!(all, vec!["two", "one"]);
+    }
+    
+    #[test]
+    fn test_capture_encoding() {
+        let repo = init_test_repo();
+        let commit_encoded = |encoding: &str, message: &[u8]| {
+            fs::write(repo.path().join("a.txt"), encoding).unwrap();
+            fs::write(repo.path().join("message.txt"), message).unwrap();
+            git(repo.path(), &["add", "a.txt"]);
+            let encoding_setting = format!("i18n.commitEncoding={}", encoding);
+            git(repo.path(), &["-c", &encoding_setting, "commit", "-q", "-F", "message.txt"]);
+        };
+        // Git converts Latin-1 itself; iconv has no "x-mac-cyrillic", so that one arrives raw.
+        commit_encoded("ISO-8859-1", b"caf\xe9 au lait\n\nna\xefve body\n");
+        commit_encoded("x-mac-cyrillic", b"\x8f\xf0\xe8\xe2\xe5\xf2\n");
+        commit_file(repo.path(), "b.txt", "two", "plain déjà vu");
         
-        if insertions == 0 && deletions == 0 {
-            insertions = stats.chars().filter(|&c| c == '+').count() as u32;
-            deletions = stats.chars().filter(|&c| c == '-').count() as u32;
+        let collector = || GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_capture_encoding(true);
+        let commits = collector().collect_history().unwrap();
+        
+        assert_eq!(commits[0].message, "plain déjà vu");
+        assert_eq!(commits[0].encoding, None);
+        assert_eq!(commits[1].message, "Привет");
+        assert_eq!(commits[1].encoding.as_deref(), Some("x-mac-cyrillic"));
+        assert_eq!(commits[2].message, "café au lait");
+        assert_eq!(commits[2].body, "naïve body");
+        assert_eq!(commits[2].encoding.as_deref(), Some("ISO-8859-1"));
+        assert_eq!(commits[2].files[0].filename, "a.txt");
+        
+        let streamed: Vec<Commit> = collector().stream_commits().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(streamed[1].message, "Привет");
+        
+        assert_eq!(collector().get_commit(&commits[1].hash).unwrap().message, "Привет");
+        let fetched = collector().get_commits(&[commits[1].hash.clone(), commits[2].hash.clone()]).unwrap();
+        assert_eq!(fetched[0].message, "Привет");
+        assert_eq!(fetched[1].body, "naïve body");
+    }
+    
+    #[test]
+    fn test_capture_signatures() {
+        let keys = tempdir().unwrap();
+        let key = keys.path().join("key");
+        let keygen = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "test", "-f"])
+            .arg(&key)
+            .output();
+        if !keygen.is_ok_and(|output| output.status.success()) {
+            return;
         }
+        let allowed_signers = keys.path().join("allowed_signers");
+        let public_key = fs::read_to_string(key.with_extension("pub")).unwrap();
+        fs::write(&allowed_signers, format!("test@example.com {}", public_key)).unwrap();
         
-        if insertions == 0 && deletions == 0 {
-            if let Some(total) = stats.split_whitespace()
-                .next()
-                .and_then(|s| s.parse::<u32>().ok()) {
-                
-                insertions = total / 2;
-                deletions = total / 2;
-                if total % 2 == 1 {
-                    insertions += 1;
-                }
-            }
+        let repo = init_test_repo();
+        git(repo.path(), &["config", "gpg.format", "ssh"]);
+        git(repo.path(), &["config", "user.signingkey", key.to_str().unwrap()]);
+        git(repo.path(), &["config", "gpg.ssh.allowedSignersFile", allowed_signers.to_str().unwrap()]);
+        fs::write(repo.path().join("a.txt"), "one").unwrap();
+        git(repo.path(), &["add", "-A"]);
+        git(repo.path(), &["commit", "-q", "-S", "-m", "signed"]);
+        commit_file(repo.path(), "b.txt", "two", "unsigned\n\nwith a body");
+        
+        let repo_path = repo.path().to_str().unwrap();
+        let commits = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_capture_signatures(true)
+            .collect_history()
+            .unwrap();
+        
+        assert_eq!(commits[0].message, "unsigned");
+        assert_eq!(commits[0].body, "with a body");
+        assert_eq!(commits[0].signature_status.as_deref(), Some("N"));
+        assert_eq!(commits[0].signer, None);
+        assert_eq!(commits[1].message, "signed");
+        assert_eq!(commits[1].signature_status.as_deref(), Some("G"));
+        assert_eq!(commits[1].signer.as_deref(), Some("test@example.com"));
+        assert!(commits[1].signing_key.as_deref().is_some_and(|key| key.starts_with("SHA256:")));
+        
+        let plain = GitCollector::new(repo_path, None, None, Vec::new()).collect_history().unwrap();
+        assert_eq!(plain[1].message, "signed");
+        assert_eq!(plain[1].signature_status, None);
+    }
+    
+    #[test]
+    fn test_git_dir_and_work_tree() {
+        let checkout = init_test_repo();
+        commit_file(checkout.path(), "a.txt", "one", "first");
+        let metadata = tempdir().unwrap();
+        let git_dir = metadata.path().join("project.git");
+        fs::rename(checkout.path().join(".git"), &git_dir).unwrap();
+        
+        let elsewhere = tempdir().unwrap();
+        let collector = |git_dir: Option<&Path>, work_tree: Option<&Path>| {
+            GitCollector::new(elsewhere.path().to_str().unwrap(), None, None, Vec::new())
+                .with_git_dir(git_dir.map(|dir| dir.display().to_string()))
+                .with_work_tree(work_tree.map(|dir| dir.display().to_string()))
+        };
+        
+        let commits = collector(Some(&git_dir), Some(checkout.path())).collect_history().unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "first");
+        
+        fs::write(checkout.path().join("a.txt"), "two").unwrap();
+        let changes = collector(Some(&git_dir), Some(checkout.path())).get_current_changes(DiffMode::Both).unwrap();
+        assert!(changes.contains_key("a.txt"));
+        
+        assert!(collector(None, Some(checkout.path())).collect_history().is_err());
+        assert!(collector(Some(&metadata.path().join("missing.git")), None).collect_history().is_err());
+    }
+    
+    #[test]
+    fn test_require_clean() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        let collect = || GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_require_clean(true)
+            .collect_history();
+        
+        assert_eq!(collect().unwrap().len(), 1);
+        
+        fs::write(repo.path().join("a.txt"), "two").unwrap();
+        fs::write(repo.path().join("new.txt"), "new").unwrap();
+        let message = collect().unwrap_err().to_string();
+        assert!(message.contains("a.txt") && message.contains("new.txt"), "{}", message);
+    }
+    
+    #[test]
+    fn test_reflog() {
+        let repo = init_test_repo();
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        assert!(collector.reflog("HEAD").unwrap().is_empty());
+        
+        commit_file(repo.path(), "a.txt", "one", "first");
+        git(repo.path(), &["commit", "-q", "--amend", "-m", "first: reworded"]);
+        
+        let entries = collector.reflog("HEAD").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].ref_name, "HEAD");
+        assert_eq!(entries[0].action, "commit (amend)");
+        assert_eq!(entries[0].message, "first: reworded");
+        assert_eq!(entries[0].old_sha.as_deref(), Some(entries[1].new_sha.as_str()));
+        assert_eq!(entries[1].action, "commit (initial)");
+        assert_eq!(entries[1].old_sha, None);
+        assert!(DateTime::parse_from_str(&entries[0].date, "%Y-%m-%d %H:%M:%S %z").is_ok());
+        
+        assert!(collector.reflog("no-such-branch").is_err());
+    }
+    
+    #[test]
+    fn test_resolve_ref() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        git(repo.path(), &["tag", "-a", "v1", "-m", "release"]);
+        commit_file(repo.path(), "a.txt", "two", "second");
+        
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let commits = collector.collect_history().unwrap();
+        
+        assert_eq!(collector.resolve_ref("HEAD").unwrap(), commits[0].hash);
+        assert_eq!(collector.resolve_ref("HEAD~1").unwrap(), commits[1].hash);
+        assert_eq!(collector.resolve_ref("v1").unwrap(), commits[1].hash);
+        assert_eq!(collector.resolve_ref(&commits[1].short_hash).unwrap().len(), 40);
+        
+        let err = collector.resolve_ref("v2").unwrap_err();
+        assert_eq!(err.to_string(), "Unknown ref: v2");
+    }
+    
+    #[test]
+    fn test_merge_base() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        git(repo.path(), &["branch", "feature"]);
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let fork_point = collector.head_sha().unwrap();
+        let main_branch = collector.current_branch().unwrap().unwrap();
+        
+        commit_file(repo.path(), "a.txt", "two", "main change");
+        git(repo.path(), &["checkout", "-q", "feature"]);
+        commit_file(repo.path(), "b.txt", "three", "feature change");
+        git(repo.path(), &["checkout", "-q", "--orphan", "unrelated"]);
+        commit_file(repo.path(), "c.txt", "four", "unrelated root");
+        
+        assert_eq!(collector.merge_base("feature", &main_branch).unwrap(), fork_point);
+        
+        let err = collector.merge_base("unrelated", "feature").unwrap_err();
+        assert!(err.to_string().contains("no common ancestor"));
+        assert!(collector.merge_base("missing", "feature").is_err());
+    }
+    
+    #[test]
+    fn test_branch_divergence() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        git(repo.path(), &["branch", "feature"]);
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        let main_branch = collector.current_branch().unwrap().unwrap();
+        
+        commit_file(repo.path(), "a.txt", "two", "main change");
+        git(repo.path(), &["checkout", "-q", "feature"]);
+        commit_file(repo.path(), "b.txt", "three", "feature change");
+        commit_file(repo.path(), "c/d.txt", "four", "another feature change");
+        
+        let divergence = collector.branch_divergence("feature", &main_branch).unwrap();
+        assert_eq!(divergence, BranchDivergence {
+            ahead: 2,
+            behind: 1,
+            branch_files: vec!["b.txt".to_string(), "c/d.txt".to_string()],
+        });
+        assert!(collector.branch_divergence("missing", &main_branch).is_err());
+    }
+    
+    #[test]
+    fn test_tag_ranges() {
+        let repo = init_test_repo();
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        commit_file(repo.path(), "a.txt", "one\n", "first");
+        git(repo.path(), &["tag", "v1.10"]);
+        assert!(collector.tag_ranges(false).unwrap().is_empty());
+        
+        commit_file(repo.path(), "a.txt", "one\ntwo\n", "second");
+        commit_file(repo.path(), "b.txt", "three\n", "third");
+        git(repo.path(), &["tag", "-a", "v1.9", "-m", "annotated"]);
+        
+        let ranges = collector.tag_ranges(true).unwrap();
+        assert_eq!(ranges, vec![TagRange {
+            from_tag: "v1.9".to_string(),
+            to_tag: "v1.10".to_string(),
+            commit_count: 0,
+            files_changed: 2,
+            insertions: 0,
+            deletions: 2,
+        }]);
+        
+        let by_date = collector.tag_ranges(false).unwrap();
+        assert_eq!(by_date.len(), 1);
+        assert_eq!((by_date[0].from_tag.as_str(), by_date[0].to_tag.as_str()), ("v1.10", "v1.9"));
+        assert_eq!(by_date[0].commit_count, 2);
+        assert_eq!(by_date[0].insertions, 2);
+    }
+    
+    #[test]
+    fn test_binary_cache_and_export() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        commit_file(repo.path(), "b.txt", "two", "second");
+        let cache_dir = tempdir().unwrap();
+        let mut collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new())
+            .with_cache_format(CacheFormat::Bincode);
+        collector.cache_dir = cache_dir.path().to_path_buf();
+        
+        let collected = collector.collect_history().unwrap();
+        let cache_file = collector.get_cache_file_path().unwrap();
+        assert_eq!(cache_file.extension().and_then(|ext| ext.to_str()), Some("bin"));
+        
+        let cached = collector.load_from_cache().unwrap().unwrap();
+        assert_eq!(cached.len(), 2);
+        assert_eq!(cached[0].hash, collected[0].hash);
+        assert_eq!(cached[1].files[0].filename, "a.txt");
+        
+        for format in [CacheFormat::Json, CacheFormat::Bincode] {
+            let export = cache_dir.path().join("export");
+            let export = export.to_str().unwrap();
+            assert_eq!(collector.export_history(export, format).unwrap(), 2);
+            
+            let loaded = GitCollector::read_export(export, format).unwrap();
+            assert_eq!(loaded.len(), 2);
+            assert_eq!(loaded[1].message, "first");
         }
         
-        (insertions, deletions)
+        let stale = bincode::serialize(&(CACHE_SCHEMA_VERSION + 1, &collected)).unwrap();
+        assert!(decode_commits(&stale, CacheFormat::Bincode).is_err());
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
     
     #[test]
-    fn test_status_to_change_count() {
-        let collector = GitCollector::new(".", None, None, Vec::new());
+    fn test_date_format() {
+        let repo = init_test_repo();
+        git(repo.path(), &["config", "log.date", "relative"]);
+        commit_file(repo.path(), "a.txt", "one", "first");
+        let repo_path = repo.path().to_str().unwrap();
         
-        assert_eq!(collector.status_to_change_count("A"), (1, 0));
-        assert_eq!(collector.status_to_change_count("D"), (0, 1));
-        assert_eq!(collector.status_to_change_count("M"), (1, 1));
-        assert_eq!(collector.status_to_change_count("R100"), (1, 1));
-        assert_eq!(collector.status_to_change_count("??"), (0, 0));
+        let pinned = GitCollector::new(repo_path, None, None, Vec::new())
+            .collect_history()
+            .unwrap();
+        assert!(DateTime::parse_from_rfc3339(&pinned[0].date).is_ok(), "{}", pinned[0].date);
+        
+        let unix = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_date_format(Some("unix".to_string()))
+            .collect_history()
+            .unwrap();
+        assert_eq!(unix[0].date, unix[0].timestamp.to_string());
     }
     
     #[test]
-    fn test_matches_file_pattern() {
-        let patterns = vec![
-            "*.rs".to_string(),
-            "src/*".to_string(),
-            "exact_file.txt".to_string()
-        ];
+    fn test_portable_cache_key() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        let mirror = tempdir().unwrap();
+        git(mirror.path(), &["clone", "-q", repo.path().to_str().unwrap(), "."]);
         
-        let collector = GitCollector::new(".", None, None, patterns);
+        let key = |path: &Path, portable: bool| {
+            GitCollector::new(path.to_str().unwrap(), None, None, Vec::new())
+                .with_portable_cache_key(portable)
+                .get_cache_key()
+                .unwrap()
+        };
         
-        assert!(collector.matches_file_pattern("main.rs"));
-        assert!(collector.matches_file_pattern("src/lib.rs"));
-        assert!(collector.matches_file_pattern("exact_file.txt"));
+        assert_ne!(key(repo.path(), false), key(mirror.path(), false));
+        assert_eq!(key(repo.path(), true), key(mirror.path(), true));
+        assert_ne!(key(repo.path(), true), key(repo.path(), false));
         
-        assert!(!collector.matches_file_pattern("main.js"));
-        assert!(!collector.matches_file_pattern("test/main.rs"));
-        assert!(!collector.matches_file_pattern("exact_file_2.txt"));
+        let empty = init_test_repo();
+        assert_eq!(key(empty.path(), true), key(empty.path(), false));
     }
     
     #[test]
-    fn test_parse_diff_stats() {
-        let collector = GitCollector::new(".", None, None, Vec::new());
+    fn test_plan_does_not_run_git() {
+        let collector = GitCollector::new("/nonexistent/repo", Some(5), None, Vec::new())
+            .with_since_last_tag(true)
+            .with_extra_log_args(vec!["--no-merges".to_string()])
+            .with_record_commands(true);
+        
+        let plan = collector.plan().unwrap();
+        
+        assert_eq!(plan[0], "log");
+        assert!(plan[1].starts_with("--pretty=format:COMMIT_START"));
+        assert!(plan.contains(&"-n 5".to_string()));
+        assert!(plan.contains(&"--no-merges".to_string()));
+        assert_eq!(plan.last().map(String::as_str), Some("<last-tag>..HEAD"));
+        assert_eq!(collector.last_command(), None);
+        
+        let rejected = GitCollector::new("/nonexistent/repo", None, None, Vec::new())
+            .with_extra_log_args(vec!["--stat".to_string()]);
+        assert!(rejected.plan().is_err());
         
-        assert_eq!(collector.parse_diff_stats("5 insertions(+), 3 deletions(-)"), (5, 3));
-        assert_eq!(collector.parse_diff_stats("2 insertions(+)"), (2, 0));
-        assert_eq!(collector.parse_diff_stats("7 deletions(-)"), (0, 7));
-        assert_eq!(collector.parse_diff_stats("++--"), (2, 2));
-        assert_eq!(collector.parse_diff_stats("10 "), (5, 5));
-        assert_eq!(collector.parse_diff_stats("11 "), (6, 5));
+        let following = GitCollector::new("/nonexistent/repo", None, None, Vec::new())
+            .with_follow_path(Some("src/lib.rs".to_string()))
+            .with_record_commands(true);
+        let plan = following.plan().unwrap();
+        assert_eq!(plan[plan.len() - 2..], ["--", "<names-of:src/lib.rs>"]);
+        assert_eq!(following.last_command(), None);
     }
     
     #[test]
-    fn test_get_cache_key() {
-        let dir = tempdir().unwrap();
-        let repo_path = dir.path().to_str().unwrap();
+    fn test_traversal_order() {
+        let order_flags = ["--date-order", "--author-date-order", "--topo-order"];
+        let default_plan = GitCollector::new("/nonexistent/repo", None, None, Vec::new()).plan().unwrap();
+        assert!(!default_plan.iter().any(|arg| order_flags.contains(&arg.as_str())));
         
-        let collector1 = GitCollector::new(repo_path, Some(10), None, Vec::new());
-        let collector2 = GitCollector::new(repo_path, Some(10), None, Vec::new());
-        let collector3 = GitCollector::new(repo_path, Some(20), None, Vec::new());
+        let plan = GitCollector::new("/nonexistent/repo", None, None, Vec::new())
+            .with_traversal_order(Some(TraversalOrder::AuthorDate))
+            .plan()
+            .unwrap();
+        assert!(plan.contains(&"--author-date-order".to_string()));
+    }
+    
+    #[test]
+    fn test_parse_shortstat() {
+        let summary = |files_changed, insertions, deletions| DiffSummary { files_changed, insertions, deletions };
         
-        let key1 = collector1.get_cache_key().unwrap();
-        let key2 = collector2.get_cache_key().unwrap();
-        let key3 = collector3.get_cache_key().unwrap();
+        assert_eq!(parse_shortstat(" 3 files changed, 10 insertions(+), 2 deletions(-)\n"), summary(3, 10, 2));
+        assert_eq!(parse_shortstat(" 1 file changed, 1 insertion(+)"), summary(1, 1, 0));
+        assert_eq!(parse_shortstat(" 1 file changed, 1 deletion(-)"), summary(1, 0, 1));
+        assert_eq!(parse_shortstat(""), summary(0, 0, 0));
         
-        assert_eq!(key1, key2);
-        assert_ne!(key1, key3);
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one\ntwo\n", "first");
+        fs::write(repo.path().join("a.txt"), "one\n2\n3\n").unwrap();
+        let collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        assert_eq!(collector.diff_summary(DiffMode::Unstaged).unwrap(), summary(1, 2, 1));
+        assert_eq!(collector.diff_summary(DiffMode::Staged).unwrap(), summary(0, 0, 0));
+    }
+    
+    #[test]
+    fn test_git_version() {
+        assert_eq!(parse_git_version("git version 2.39.2\n"), Some((2, 39, 2)));
+        assert_eq!(parse_git_version("git version 2.37.1 (Apple Git-137.1)"), Some((2, 37, 1)));
+        assert_eq!(parse_git_version("git version 2.41.0.windows.1"), Some((2, 41, 0)));
+        assert_eq!(parse_git_version("git version 1.8"), Some((1, 8, 0)));
+        assert_eq!(parse_git_version("not git"), None);
+        
+        let collector = GitCollector::new(".", None, None, Vec::new()).with_record_commands(true);
+        let version = collector.git_version().unwrap().unwrap();
+        assert!(version >= MIN_GIT_VERSION);
+        assert_eq!(collector.last_command().as_deref(), Some("git --no-pager --version"));
+        
+        *collector.last_command.lock().unwrap() = None;
+        assert_eq!(collector.git_version().unwrap(), Some(version));
+        assert_eq!(collector.last_command(), None);
+        assert!(collector.check_git_version().is_ok());
+    }
+    
+    #[test]
+    fn test_warm_cache() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        commit_file(repo.path(), "b.txt", "two", "second");
+        let cache_dir = tempdir().unwrap();
+        let mut collector = GitCollector::new(repo.path().to_str().unwrap(), None, None, Vec::new());
+        collector.cache_dir = cache_dir.path().to_path_buf();
+        
+        let first = collector.warm_cache().unwrap();
+        assert_eq!((first.commit_count, first.rebuilt), (2, true));
+        
+        let second = collector.warm_cache().unwrap();
+        assert_eq!((second.commit_count, second.rebuilt), (2, false));
+    }
+    
+    #[test]
+    fn test_stream_commits_with_hard_cap() {
+        let repo = init_test_repo();
+        for n in 0..5 {
+            commit_file(repo.path(), &format!("{}.txt", n), "content", &format!("commit {}", n));
+        }
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let collected = GitCollector::new(repo_path, None, None, Vec::new()).collect_history().unwrap();
+        let streamed: Vec<Commit> = GitCollector::new(repo_path, None, None, Vec::new())
+            .stream_commits()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            streamed.iter().map(|commit| (&commit.hash, commit.index, commit.files.len())).collect::<Vec<_>>(),
+            collected.iter().map(|commit| (&commit.hash, commit.index, commit.files.len())).collect::<Vec<_>>()
+        );
+        
+        let mut capped = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_hard_commit_cap(Some(2))
+            .stream_commits()
+            .unwrap();
+        assert_eq!(capped.next().unwrap().unwrap().message, "commit 4");
+        assert_eq!(capped.next().unwrap().unwrap().message, "commit 3");
+        assert!(capped.next().is_none());
+        assert!(capped.child.try_wait().unwrap().is_some());
+        
+        let failing = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_extra_log_args(vec!["no-such-branch".to_string()])
+            .stream_commits()
+            .unwrap()
+            .collect::<Result<Vec<_>>>();
+        assert!(failing.is_err());
+        
+        assert!(GitCollector::new(repo_path, None, None, Vec::new())
+            .with_max_commits_per_author(Some(1))
+            .stream_commits()
+            .is_err());
+    }
+    
+    #[test]
+    fn test_extra_log_args() {
+        let repo = init_test_repo();
+        commit_file(repo.path(), "a.txt", "one", "first");
+        commit_file(repo.path(), "a.txt", "two", "second");
+        let repo_path = repo.path().to_str().unwrap();
+        
+        let collector = GitCollector::new(repo_path, None, None, Vec::new())
+            .with_extra_log_args(vec!["--grep=second".to_string()]);
+        let commits = collector.collect_history().unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "second");
+        
+        for reserved in ["--pretty=oneline", "--name-only", "-p", "--graph", "-s"] {
+            let collector = GitCollector::new(repo_path, None, None, Vec::new())
+                .with_extra_log_args(vec![reserved.to_string()]);
+            assert!(collector.collect_history().is_err(), "{} should be rejected", reserved);
+        }
+    }
+    
+    #[test]
+    fn test_is_shallow() {
+        let origin = init_test_repo();
+        commit_file(origin.path(), "a.txt", "one", "first");
+        commit_file(origin.path(), "a.txt", "two", "second");
+        
+        let full = GitCollector::new(origin.path().to_str().unwrap(), None, None, Vec::new());
+        assert!(!full.is_shallow().unwrap());
+        
+        let clone_parent = tempdir().unwrap();
+        let url = format!("file://{}", origin.path().display());
+        git(clone_parent.path(), &["clone", "-q", "--depth", "1", &url, "clone"]);
+        let clone_path = clone_parent.path().join("clone");
+        
+        let shallow = GitCollector::new(clone_path.to_str().unwrap(), None, None, Vec::new());
+        assert!(shallow.is_shallow().unwrap());
+        
+        let strict = || GitCollector::new(clone_path.to_str().unwrap(), None, None, Vec::new())
+            .with_fail_on_shallow(true);
+        assert!(strict().collect_history().is_err());
+        assert!(strict().stream_commits().is_err());
+    }
+    
+    #[test]
+    fn test_fast_path() {
+        let origin = init_test_repo();
+        commit_file(origin.path(), "a.txt", "one", "first");
+        commit_file(origin.path(), "a.txt", "two", "second");
+        let clone_parent = tempdir().unwrap();
+        let url = format!("file://{}", origin.path().display());
+        git(clone_parent.path(), &["clone", "-q", "--depth", "1", &url, "clone"]);
+        let clone_path = clone_parent.path().join("clone");
+        
+        let fast = GitCollector::new(clone_path.to_str().unwrap(), None, None, Vec::new())
+            .with_fail_on_shallow(true)
+            .with_fast_path(true);
+        assert_eq!(fast.collect_history().unwrap().len(), 1);
+        
+        let repo_path = origin.path().to_str().unwrap();
+        let respelled = format!("{}/.", repo_path);
+        let key = |path: &str, fast_path: bool| GitCollector::new(path, None, None, Vec::new())
+            .with_fast_path(fast_path)
+            .get_cache_key()
+            .unwrap();
+        assert_eq!(key(repo_path, false), key(&respelled, false));
+        assert_ne!(key(repo_path, true), key(&respelled, true));
+        
+        let missing_work_tree = |fast_path: bool| GitCollector::new(repo_path, None, None, Vec::new())
+            .with_git_dir(Some(".git".to_string()))
+            .with_work_tree(Some("missing".to_string()))
+            .with_fast_path(fast_path)
+            .list_tracked_files();
+        assert!(missing_work_tree(false).unwrap_err().to_string().contains("not a directory"));
+        assert!(missing_work_tree(true).is_ok_and(|files| files == ["a.txt"]));
     }
 }
@@ -1,17 +1,30 @@
+// pyo3 0.19's macros emit impls that newer compilers flag as non-local.
+#![allow(non_local_definitions)]
+
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use chrono::Utc;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
+mod analysis;
 mod git_collector;
 mod error;
 mod models;
 
-use crate::git_collector::GitCollector;
-use crate::models::Commit;
+use crate::git_collector::{collect_repositories, map_repositories, CommitStream, GitCollector};
+use crate::models::{
+    AuthorEdge, CacheFormat, Commit, DiffMode, DirectorySort, FileChurn, FileOwnership, MessageRules, RevertPair,
+    TestCategory, TraversalOrder,
+};
 
 #[pymodule]
 fn gitsect(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<RustGitCollector>()?;
+    m.add_class::<CommitIterator>()?;
+    m.add_class::<CommitBatchIterator>()?;
     Ok(())
 }
 
@@ -21,78 +34,1162 @@ struct RustGitCollector {
     max_commits: Option<u32>,
     since_days: Option<u32>,
     file_patterns: Vec<String>,
+    fail_on_shallow: bool,
+    extra_log_args: Vec<String>,
+    abbrev_length: Option<u8>,
+    use_mailmap: Option<bool>,
+    normalize_rename_churn: bool,
+    max_commits_per_author: Option<usize>,
+    record_commands: bool,
+    since_last_tag: bool,
+    include_merge_diffs: bool,
+    max_output_bytes: Option<usize>,
+    exclude_authors: Vec<String>,
+    detect_renames: Option<bool>,
+    detect_copies: bool,
+    rename_threshold: Option<u8>,
+    status_filter: Option<Vec<char>>,
+    cache_format: CacheFormat,
+    date_format: Option<String>,
+    portable_cache_key: bool,
+    min_files: Option<usize>,
+    include_empty: bool,
+    strip_path_prefix: Option<String>,
+    code_extensions: Option<Vec<String>>,
+    hard_commit_cap: Option<usize>,
+    traversal_order: Option<TraversalOrder>,
+    author_aliases: HashMap<String, String>,
+    pathspecs: Vec<String>,
+    require_clean: bool,
+    commit_filter: Option<PyObject>,
+    capture_signatures: bool,
+    capture_encoding: bool,
+    git_dir: Option<String>,
+    work_tree: Option<String>,
+    max_file_lines: Option<u32>,
+    follow_path: Option<String>,
+    detect_whitespace_only: bool,
+    fast_path: bool,
+    record_markers: Option<(String, String)>,
+    ref_name: Option<String>,
+    since_date: Option<String>,
+    until_date: Option<String>,
+    ascending: bool,
+    extensions: Vec<String>,
+    exclude_paths: Vec<String>,
+    last_command: Arc<Mutex<Option<String>>>,
+}
+
+impl RustGitCollector {
+    fn collector(&self) -> GitCollector {
+        self.collector_for(&self.repo_path)
+    }
+    
+    fn collector_for(&self, repo_path: &str) -> GitCollector {
+        GitCollector::new(
+            repo_path,
+            self.max_commits,
+            self.since_days,
+            self.file_patterns.clone()
+        )
+        .with_fail_on_shallow(self.fail_on_shallow)
+        .with_extra_log_args(self.extra_log_args.clone())
+        .with_abbrev_length(self.abbrev_length)
+        .with_use_mailmap(self.use_mailmap)
+        .with_normalize_rename_churn(self.normalize_rename_churn)
+        .with_max_commits_per_author(self.max_commits_per_author)
+        .with_record_commands(self.record_commands)
+        .with_command_log(self.last_command.clone())
+        .with_since_last_tag(self.since_last_tag)
+        .with_include_merge_diffs(self.include_merge_diffs)
+        .with_max_output_bytes(self.max_output_bytes)
+        .with_exclude_authors(self.exclude_authors.clone())
+        .with_detect_renames(self.detect_renames)
+        .with_detect_copies(self.detect_copies)
+        .with_rename_threshold(self.rename_threshold)
+        .with_status_filter(self.status_filter.clone())
+        .with_cache_format(self.cache_format)
+        .with_traversal_order(self.traversal_order)
+        .with_author_aliases(self.author_aliases.clone())
+        .with_pathspecs(self.pathspecs.clone())
+        .with_require_clean(self.require_clean)
+        .with_capture_signatures(self.capture_signatures)
+        .with_capture_encoding(self.capture_encoding)
+        .with_git_dir(self.git_dir.clone())
+        .with_work_tree(self.work_tree.clone())
+        .with_max_file_lines(self.max_file_lines)
+        .with_follow_path(self.follow_path.clone())
+        .with_detect_whitespace_only(self.detect_whitespace_only)
+        .with_fast_path(self.fast_path)
+        .with_record_markers(self.record_markers.clone())
+        .with_ref_name(self.ref_name.clone())
+        .with_since_date(self.since_date.clone())
+        .with_until_date(self.until_date.clone())
+        .with_ascending(self.ascending)
+        .with_extensions(self.extensions.clone())
+        .with_exclude_paths(self.exclude_paths.clone())
+        .with_date_format(self.date_format.clone())
+        .with_portable_cache_key(self.portable_cache_key)
+        .with_min_files(self.min_files)
+        .with_include_empty(self.include_empty)
+        .with_strip_path_prefix(self.strip_path_prefix.clone())
+        .with_code_extensions(self.code_extensions.clone())
+        .with_hard_commit_cap(self.hard_commit_cap)
+    }
+    
+    fn collect_commits(&self) -> PyResult<Vec<Commit>> {
+        self.collect_commits_with(self.collector())
+    }
+    
+    fn collect_commits_with(&self, collector: GitCollector) -> PyResult<Vec<Commit>> {
+        let commits = collector.collect_history().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to collect history: {}", err))
+        })?;
+        Python::with_gil(|py| self.filter_commits(py, commits))
+    }
+    
+    // Every method that hands back commits goes through here (streams use next_commit_dict).
+    // The predicate runs once per commit with the GIL held after the parallel parse, building a
+    // full commit dict each time, so on large histories it can cost more than the parse itself.
+    fn filter_commits(&self, py: Python, commits: Vec<Commit>) -> PyResult<Vec<Commit>> {
+        let Some(predicate) = &self.commit_filter else {
+            return Ok(commits);
+        };
+        
+        let mut kept = Vec::with_capacity(commits.len());
+        for commit in commits {
+            if keeps_commit(py, predicate, &commit)? {
+                kept.push(commit);
+            }
+        }
+        Ok(kept)
+    }
+}
+
+#[pyclass(name = "CommitIterator")]
+struct CommitIterator {
+    stream: CommitStream,
+    commit_filter: Option<PyObject>,
+}
+
+#[pymethods]
+impl CommitIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    
+    fn __next__(mut slf: PyRefMut<'_, Self>, py: Python) -> PyResult<Option<PyObject>> {
+        let CommitIterator { stream, commit_filter } = &mut *slf;
+        next_commit_dict(py, stream, commit_filter.as_ref())
+    }
+}
+
+#[pyclass(name = "CommitBatchIterator")]
+struct CommitBatchIterator {
+    stream: CommitStream,
+    commit_filter: Option<PyObject>,
+    batch_size: usize,
+}
+
+#[pymethods]
+impl CommitBatchIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+    
+    fn __next__(mut slf: PyRefMut<'_, Self>, py: Python) -> PyResult<Option<PyObject>> {
+        let CommitBatchIterator { stream, commit_filter, batch_size } = &mut *slf;
+        
+        let batch = PyList::empty(py);
+        while batch.len() < *batch_size {
+            match next_commit_dict(py, stream, commit_filter.as_ref())? {
+                Some(commit_dict) => batch.append(commit_dict)?,
+                None => break,
+            }
+        }
+        
+        // The last batch may be partial; an empty one means the stream is done.
+        Ok((!batch.is_empty()).then(|| batch.into()))
+    }
 }
 
 #[pymethods]
 impl RustGitCollector {
     #[new]
-    #[pyo3(signature = (repo_path = ".", max_commits = None, since_days = None, file_patterns = None))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        repo_path = ".",
+        max_commits = None,
+        since_days = None,
+        file_patterns = None,
+        fail_on_shallow = false,
+        patterns_file = None,
+        extra_log_args = None,
+        abbrev_length = None,
+        use_mailmap = None,
+        normalize_rename_churn = false,
+        max_commits_per_author = None,
+        record_commands = false,
+        since_last_tag = false,
+        include_merge_diffs = false,
+        max_output_bytes = None,
+        exclude_authors = None,
+        detect_renames = None,
+        detect_copies = false,
+        rename_threshold = None,
+        status_filter = None,
+        cache_format = "json",
+        date_format = None,
+        portable_cache_key = false,
+        min_files = None,
+        include_empty = false,
+        strip_path_prefix = None,
+        code_extensions = None,
+        hard_commit_cap = None,
+        traversal_order = None,
+        author_aliases = None,
+        pathspecs = None,
+        require_clean = false,
+        commit_filter = None,
+        capture_signatures = false,
+        capture_encoding = false,
+        git_dir = None,
+        work_tree = None,
+        max_file_lines = None,
+        follow_path = None,
+        detect_whitespace_only = false,
+        fast_path = false,
+        record_markers = None,
+        ref_name = None,
+        since_date = None,
+        until_date = None,
+        ascending = false,
+        extensions = None,
+        exclude_paths = None
+    ))]
     fn new(
         repo_path: &str, 
         max_commits: Option<u32>, 
         since_days: Option<u32>,
-        file_patterns: Option<Vec<String>>
-    ) -> Self {
-        RustGitCollector {
+        file_patterns: Option<Vec<String>>,
+        fail_on_shallow: bool,
+        patterns_file: Option<&str>,
+        extra_log_args: Option<Vec<String>>,
+        abbrev_length: Option<u8>,
+        use_mailmap: Option<bool>,
+        normalize_rename_churn: bool,
+        max_commits_per_author: Option<usize>,
+        record_commands: bool,
+        since_last_tag: bool,
+        include_merge_diffs: bool,
+        max_output_bytes: Option<usize>,
+        exclude_authors: Option<Vec<String>>,
+        detect_renames: Option<bool>,
+        detect_copies: bool,
+        rename_threshold: Option<u8>,
+        status_filter: Option<Vec<char>>,
+        cache_format: &str,
+        date_format: Option<String>,
+        portable_cache_key: bool,
+        min_files: Option<usize>,
+        include_empty: bool,
+        strip_path_prefix: Option<String>,
+        code_extensions: Option<Vec<String>>,
+        hard_commit_cap: Option<usize>,
+        traversal_order: Option<&str>,
+        author_aliases: Option<HashMap<String, String>>,
+        pathspecs: Option<Vec<String>>,
+        require_clean: bool,
+        commit_filter: Option<PyObject>,
+        capture_signatures: bool,
+        capture_encoding: bool,
+        git_dir: Option<String>,
+        work_tree: Option<String>,
+        max_file_lines: Option<u32>,
+        follow_path: Option<String>,
+        detect_whitespace_only: bool,
+        fast_path: bool,
+        record_markers: Option<(String, String)>,
+        ref_name: Option<String>,
+        since_date: Option<String>,
+        until_date: Option<String>,
+        ascending: bool,
+        extensions: Option<Vec<String>>,
+        exclude_paths: Option<Vec<String>>
+    ) -> PyResult<Self> {
+        let mut file_patterns = file_patterns.unwrap_or_default();
+        
+        if let Some(path) = patterns_file {
+            let file_patterns_from_file = GitCollector::read_patterns_file(path).map_err(|err| {
+                PyRuntimeError::new_err(format!("Failed to read patterns file: {}", err))
+            })?;
+            
+            for pattern in file_patterns_from_file {
+                if !file_patterns.contains(&pattern) {
+                    file_patterns.push(pattern);
+                }
+            }
+        }
+        
+        Ok(RustGitCollector {
             repo_path: repo_path.to_string(),
             max_commits,
             since_days,
-            file_patterns: file_patterns.unwrap_or_else(Vec::new),
-        }
+            file_patterns,
+            fail_on_shallow,
+            extra_log_args: extra_log_args.unwrap_or_default(),
+            abbrev_length,
+            use_mailmap,
+            normalize_rename_churn,
+            max_commits_per_author,
+            record_commands,
+            since_last_tag,
+            include_merge_diffs,
+            max_output_bytes,
+            exclude_authors: exclude_authors.unwrap_or_default(),
+            detect_renames,
+            detect_copies,
+            rename_threshold,
+            status_filter,
+            cache_format: parse_cache_format(cache_format)?,
+            date_format,
+            portable_cache_key,
+            min_files,
+            include_empty,
+            strip_path_prefix,
+            code_extensions,
+            hard_commit_cap,
+            traversal_order: traversal_order.map(parse_traversal_order).transpose()?,
+            author_aliases: author_aliases.unwrap_or_default(),
+            pathspecs: pathspecs.unwrap_or_default(),
+            require_clean,
+            commit_filter,
+            capture_signatures,
+            capture_encoding,
+            git_dir,
+            work_tree,
+            max_file_lines,
+            follow_path,
+            detect_whitespace_only,
+            fast_path,
+            record_markers,
+            ref_name,
+            since_date,
+            until_date,
+            ascending,
+            extensions: extensions.unwrap_or_default(),
+            exclude_paths: exclude_paths.unwrap_or_default(),
+            last_command: Arc::new(Mutex::new(None)),
+        })
     }
 
     fn collect_history(&self, py: Python) -> PyResult<PyObject> {
-        let collector = GitCollector::new(
-            &self.repo_path, 
-            self.max_commits, 
-            self.since_days,
-            self.file_patterns.clone()
-        );
+        let result = PyList::empty(py);
+        for commit in self.collect_commits()? {
+            let commit_dict = commit_to_py_dict(py, &commit)?;
+            result.append(commit_dict)?;
+        }
+        Ok(result.into())
+    }
+
+    #[pyo3(signature = (author_email, only_author = false))]
+    fn history_since_author_joined(&self, py: Python, author_email: &str, only_author: bool) -> PyResult<PyObject> {
+        let joined = self.collector().author_first_timestamp(author_email).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to resolve first commit: {}", err))
+        })?;
+        let Some(joined) = joined else {
+            return Err(PyValueError::new_err(format!("No commits by {}", author_email)));
+        };
         
-        match collector.collect_history() {
-            Ok(commits) => {
-                let result = PyList::empty(py);
-                for commit in commits {
-                    let commit_dict = commit_to_py_dict(py, &commit)?;
-                    result.append(commit_dict)?;
-                }
-                Ok(result.into())
-            },
-            Err(err) => {
-                Err(PyRuntimeError::new_err(format!("Failed to collect history: {}", err)))
+        // git applies the cut-off, so max_commits counts from the join date on. A since_days window
+        // that starts later is kept; a since_date can't be compared here and is kept as given.
+        let days_cutoff = self.since_days.map(|days| Utc::now().timestamp() - i64::from(days) * 86_400);
+        let collector = if self.since_date.is_none() && days_cutoff.is_none_or(|cutoff| cutoff < joined) {
+            self.collector().with_since_days(None).with_since_date(Some(format!("@{}", joined)))
+        } else {
+            self.collector()
+        };
+        let collector = collector.with_author_email(only_author.then(|| author_email.to_string()));
+        
+        let result = PyList::empty(py);
+        for commit in self.collect_commits_with(collector)? {
+            // `--since` compares commit dates, so commits authored before the join date can slip in.
+            if commit.timestamp < joined {
+                continue;
             }
+            result.append(commit_to_py_dict(py, &commit)?)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn collect_submodule_history(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        let path = self.collector().submodule_path(name).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to resolve submodule: {}", err))
+        })?;
+        
+        let commits = self.collector_for(&path.to_string_lossy()).collect_history().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to collect submodule history: {}", err))
+        })?;
+        let commits = self.filter_commits(py, commits)?;
+        
+        let result = PyList::empty(py);
+        for commit in &commits {
+            result.append(commit_to_py_dict(py, commit)?)?;
         }
+        Ok(result.into())
     }
 
-    fn get_current_changes(&self, py: Python) -> PyResult<PyObject> {
-        let collector = GitCollector::new(
-            &self.repo_path, 
-            None, 
-            None,
-            self.file_patterns.clone()
-        );
+    fn warm_cache(&self, py: Python) -> PyResult<PyObject> {
+        let status = self.collector().warm_cache().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to warm cache: {}", err))
+        })?;
         
-        match collector.get_current_changes() {
-            Ok(changes) => {
-                let result = PyDict::new(py);
-                
-                for (file, change_data) in changes {
-                    let data_dict = PyDict::new(py);
-                    
-                    for (key, value) in change_data {
-                        data_dict.set_item(key, value)?;
+        let status_dict = PyDict::new(py);
+        status_dict.set_item("commit_count", status.commit_count)?;
+        status_dict.set_item("rebuilt", status.rebuilt)?;
+        Ok(status_dict.into())
+    }
+
+    fn stream_history(&self, py: Python) -> PyResult<CommitIterator> {
+        let stream = self.collector().stream_commits().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to stream history: {}", err))
+        })?;
+        let commit_filter = self.commit_filter.as_ref().map(|predicate| predicate.clone_ref(py));
+        Ok(CommitIterator { stream, commit_filter })
+    }
+
+    #[pyo3(signature = (batch_size = 500))]
+    fn stream_history_batches(&self, py: Python, batch_size: usize) -> PyResult<CommitBatchIterator> {
+        if batch_size == 0 {
+            return Err(PyValueError::new_err("batch_size must be positive"));
+        }
+        let stream = self.collector().stream_commits().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to stream history: {}", err))
+        })?;
+        let commit_filter = self.commit_filter.as_ref().map(|predicate| predicate.clone_ref(py));
+        Ok(CommitBatchIterator { stream, commit_filter, batch_size })
+    }
+
+    /// Pushes each streamed commit to `callback` as soon as it is parsed and returns how many
+    /// were delivered. An exception raised by the callback stops git and propagates unchanged.
+    fn for_each_commit(&self, py: Python, callback: PyObject) -> PyResult<usize> {
+        if !callback.as_ref(py).is_callable() {
+            return Err(PyValueError::new_err("callback must be callable"));
+        }
+        let mut stream = self.collector().stream_commits().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to stream history: {}", err))
+        })?;
+        
+        let mut delivered = 0;
+        while let Some(commit_dict) = next_commit_dict(py, &mut stream, self.commit_filter.as_ref())? {
+            callback.call1(py, (commit_dict,))?;
+            delivered += 1;
+        }
+        Ok(delivered)
+    }
+
+    #[pyo3(signature = (repo_paths, max_parallel = 4))]
+    fn collect_repositories(&self, py: Python, repo_paths: Vec<String>, max_parallel: usize) -> PyResult<PyObject> {
+        let results = collect_repositories(&repo_paths, max_parallel, |repo_path| {
+            self.collector_for(repo_path)
+        })
+        .map_err(|err| PyRuntimeError::new_err(format!("Failed to collect repositories: {}", err)))?;
+        
+        let result = PyDict::new(py);
+        for (repo_path, outcome) in results {
+            let repo_dict = PyDict::new(py);
+            
+            match outcome {
+                Ok(commits) => {
+                    let commit_list = PyList::empty(py);
+                    for commit in &self.filter_commits(py, commits)? {
+                        commit_list.append(commit_to_py_dict(py, commit)?)?;
                     }
-                    
-                    result.set_item(file, data_dict)?;
+                    repo_dict.set_item("commits", commit_list)?;
+                    repo_dict.set_item("error", py.None())?;
+                },
+                Err(err) => {
+                    repo_dict.set_item("commits", py.None())?;
+                    repo_dict.set_item("error", err.to_string())?;
                 }
-                
-                Ok(result.into())
-            },
+            }
+            
+            result.set_item(repo_path, repo_dict)?;
+        }
+        Ok(result.into())
+    }
+
+    #[pyo3(signature = (mode = "unstaged"))]
+    fn get_current_changes(&self, py: Python, mode: &str) -> PyResult<PyObject> {
+        let mode = parse_diff_mode(mode)?;
+        
+        match self.collector().get_current_changes(mode) {
+            Ok(changes) => changes_to_py_dict(py, changes),
             Err(err) => {
                 Err(PyRuntimeError::new_err(format!("Failed to get current changes: {}", err)))
             }
         }
     }
+    
+    #[pyo3(signature = (mode = "unstaged"))]
+    fn diff_summary(&self, py: Python, mode: &str) -> PyResult<PyObject> {
+        let summary = self.collector().diff_summary(parse_diff_mode(mode)?).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to summarize changes: {}", err))
+        })?;
+        
+        let result = PyDict::new(py);
+        result.set_item("files_changed", summary.files_changed)?;
+        result.set_item("insertions", summary.insertions)?;
+        result.set_item("deletions", summary.deletions)?;
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (repo_paths, mode = "unstaged", max_parallel = 4))]
+    fn get_current_changes_across(
+        &self,
+        py: Python,
+        repo_paths: Vec<String>,
+        mode: &str,
+        max_parallel: usize
+    ) -> PyResult<PyObject> {
+        let mode = parse_diff_mode(mode)?;
+        let results = map_repositories(
+            &repo_paths,
+            max_parallel,
+            |repo_path| self.collector_for(repo_path),
+            |collector| collector.get_current_changes(mode)
+        )
+        .map_err(|err| PyRuntimeError::new_err(format!("Failed to get current changes: {}", err)))?;
+        
+        let result = PyDict::new(py);
+        for (repo_path, outcome) in results {
+            let repo_dict = PyDict::new(py);
+            
+            match outcome {
+                Ok(changes) => {
+                    repo_dict.set_item("changes", changes_to_py_dict(py, changes)?)?;
+                    repo_dict.set_item("error", py.None())?;
+                },
+                Err(err) => {
+                    repo_dict.set_item("changes", py.None())?;
+                    repo_dict.set_item("error", err.to_string())?;
+                }
+            }
+            
+            result.set_item(repo_path, repo_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn working_tree_diff(&self, py: Python, reference: &str) -> PyResult<PyObject> {
+        let changes = self.collector().working_tree_diff(reference).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to diff working tree: {}", err))
+        })?;
+        changes_to_py_dict(py, changes)
+    }
+
+    #[pyo3(signature = (half_life_days = None))]
+    fn file_churn(&self, py: Python, half_life_days: Option<f64>) -> PyResult<PyObject> {
+        if half_life_days.is_some_and(|half_life| !(half_life.is_finite() && half_life > 0.0)) {
+            return Err(PyValueError::new_err("half_life_days must be a positive finite number"));
+        }
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for churn in analysis::file_churn(&commits, half_life_days, Utc::now().timestamp()) {
+            result.append(file_churn_to_py_dict(py, &churn)?)?;
+        }
+        Ok(result.into())
+    }
+
+    #[pyo3(signature = (n = 20))]
+    fn top_churned_files(&self, py: Python, n: usize) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for churn in analysis::top_churned_files(&commits, n) {
+            result.append(file_churn_to_py_dict(py, &churn)?)?;
+        }
+        Ok(result.into())
+    }
+
+    #[pyo3(signature = (unstable_threshold = 5.0))]
+    fn relative_churn(&self, py: Python, unstable_threshold: f64) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        let line_counts = self.collector().line_counts().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to count lines: {}", err))
+        })?;
+        
+        let churn = analysis::file_churn(&commits, None, Utc::now().timestamp());
+        
+        let result = PyList::empty(py);
+        for file in analysis::relative_churn(&churn, &line_counts, unstable_threshold) {
+            let file_dict = PyDict::new(py);
+            file_dict.set_item("filename", &file.filename)?;
+            file_dict.set_item("churn", file.churn)?;
+            file_dict.set_item("lines", file.lines)?;
+            file_dict.set_item("relative_churn", file.relative_churn)?;
+            file_dict.set_item("unstable", file.unstable)?;
+            result.append(file_dict)?;
+        }
+        Ok(result.into())
+    }
+
+    fn file_ownership(&self, py: Python) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for ownership in analysis::file_ownership(&commits) {
+            result.append(file_ownership_to_py_dict(py, &ownership)?)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn commits_touching_owned_files(&self, py: Python, owner_email: &str) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for touched in analysis::commits_touching_owned_files(&commits, owner_email) {
+            let commit_dict = commit_to_py_dict(py, &touched.commit)?;
+            commit_dict.as_ref(py).downcast::<PyDict>()?.set_item("owned_files", touched.owned_files)?;
+            result.append(commit_dict)?;
+        }
+        Ok(result.into())
+    }
+
+    fn collaboration_graph(&self, py: Python) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for edge in analysis::collaboration_graph(&commits) {
+            result.append(author_edge_to_py_dict(py, &edge)?)?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (min_coupling = 0.5, min_cluster_size = 2))]
+    fn co_change_clusters(&self, py: Python, min_coupling: f64, min_cluster_size: usize) -> PyResult<PyObject> {
+        if !(min_coupling > 0.0 && min_coupling <= 1.0) {
+            return Err(PyValueError::new_err("min_coupling must be in (0, 1]"));
+        }
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for cluster in analysis::co_change_clusters(&commits, min_coupling, min_cluster_size) {
+            result.append(PyList::new(py, cluster))?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (exclude_merges = false, exclude_binary_only = false))]
+    fn commit_size_stats(&self, py: Python, exclude_merges: bool, exclude_binary_only: bool) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        let stats = analysis::commit_size_stats(&commits, exclude_merges, exclude_binary_only);
+        
+        let stats_dict = PyDict::new(py);
+        stats_dict.set_item("commit_count", stats.commit_count)?;
+        stats_dict.set_item("mean", stats.mean)?;
+        stats_dict.set_item("max", stats.max)?;
+        stats_dict.set_item("p50", stats.p50)?;
+        stats_dict.set_item("p90", stats.p90)?;
+        stats_dict.set_item("p99", stats.p99)?;
+        Ok(stats_dict.into())
+    }
+    
+    fn commits_by_file(&self) -> PyResult<HashMap<String, Vec<String>>> {
+        let commits = self.collect_commits()?;
+        Ok(analysis::commits_by_file(&commits))
+    }
+    
+    fn commit_cadence(&self, py: Python) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        
+        let result = PyDict::new(py);
+        for (author, cadence) in analysis::commit_cadence(&commits) {
+            let cadence_dict = PyDict::new(py);
+            cadence_dict.set_item("median_gap_hours", cadence.median_gap_hours)?;
+            cadence_dict.set_item("commit_count", cadence.commit_count)?;
+            result.set_item(author, cadence_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (count_lines = false))]
+    fn author_month_matrix(&self, py: Python, count_lines: bool) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        let matrix = analysis::author_month_matrix(&commits, count_lines);
+        
+        let result = PyDict::new(py);
+        result.set_item("authors", matrix.authors)?;
+        result.set_item("months", matrix.months)?;
+        result.set_item("counts", matrix.counts)?;
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (bucket_days = 7, smoothing_buckets = 4))]
+    fn churn_velocity(&self, py: Python, bucket_days: u32, smoothing_buckets: usize) -> PyResult<PyObject> {
+        if bucket_days == 0 || smoothing_buckets == 0 {
+            return Err(PyValueError::new_err("bucket_days and smoothing_buckets must be positive"));
+        }
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for point in analysis::churn_velocity(&commits, bucket_days, smoothing_buckets) {
+            let point_dict = PyDict::new(py);
+            point_dict.set_item("bucket_start", point.bucket_start)?;
+            point_dict.set_item("churn", point.churn)?;
+            point_dict.set_item("velocity", point.velocity)?;
+            result.append(point_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (bucket_days = 30))]
+    fn extension_churn_trend(&self, py: Python, bucket_days: u32) -> PyResult<PyObject> {
+        if bucket_days == 0 {
+            return Err(PyValueError::new_err("bucket_days must be positive"));
+        }
+        let commits = self.collect_commits()?;
+        
+        let result = PyDict::new(py);
+        for (extension, series) in analysis::extension_churn_trend(&commits, bucket_days) {
+            let buckets = PyList::empty(py);
+            for bucket in series {
+                let bucket_dict = PyDict::new(py);
+                bucket_dict.set_item("bucket_start", bucket.bucket_start)?;
+                bucket_dict.set_item("additions", bucket.additions)?;
+                bucket_dict.set_item("deletions", bucket.deletions)?;
+                buckets.append(bucket_dict)?;
+            }
+            result.set_item(extension, buckets)?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (bucket_days = 30))]
+    fn growth_ratio_trend(&self, py: Python, bucket_days: u32) -> PyResult<PyObject> {
+        if bucket_days == 0 {
+            return Err(PyValueError::new_err("bucket_days must be positive"));
+        }
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for point in analysis::growth_ratio_trend(&commits, bucket_days) {
+            let point_dict = PyDict::new(py);
+            point_dict.set_item("bucket_start", point.bucket_start)?;
+            point_dict.set_item("additions", point.additions)?;
+            point_dict.set_item("deletions", point.deletions)?;
+            point_dict.set_item("ratio", point.ratio)?;
+            result.append(point_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (timezone = "utc"))]
+    fn punchcard(&self, timezone: &str) -> PyResult<Vec<Vec<u32>>> {
+        let utc_offset_minutes = parse_timezone(timezone)?;
+        let commits = self.collect_commits()?;
+        Ok(analysis::punchcard(&commits, utc_offset_minutes))
+    }
+    
+    #[pyo3(signature = (depth = 1))]
+    fn directory_truck_factor(&self, py: Python, depth: usize) -> PyResult<PyObject> {
+        if depth == 0 {
+            return Err(PyValueError::new_err("depth must be at least 1"));
+        }
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for directory in analysis::directory_truck_factor(&commits, depth) {
+            let directory_dict = PyDict::new(py);
+            directory_dict.set_item("directory", &directory.directory)?;
+            directory_dict.set_item("truck_factor", directory.truck_factor)?;
+            directory_dict.set_item("key_authors", &directory.key_authors)?;
+            directory_dict.set_item("total_changes", directory.total_changes)?;
+            directory_dict.set_item("high_risk", directory.high_risk)?;
+            result.append(directory_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (depth = 1, sort_by = "churn"))]
+    fn directory_report(&self, py: Python, depth: usize, sort_by: &str) -> PyResult<PyObject> {
+        if depth == 0 {
+            return Err(PyValueError::new_err("depth must be at least 1"));
+        }
+        let sort = match sort_by {
+            "churn" => DirectorySort::Churn,
+            "staleness" => DirectorySort::Staleness,
+            other => return Err(PyValueError::new_err(format!(
+                "Unknown sort_by '{}', expected 'churn' or 'staleness'", other
+            ))),
+        };
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for directory in analysis::directory_report(&commits, depth, sort) {
+            let directory_dict = PyDict::new(py);
+            directory_dict.set_item("directory", &directory.directory)?;
+            directory_dict.set_item("dominant_author", &directory.dominant_author)?;
+            directory_dict.set_item("ownership_ratio", directory.ownership_ratio)?;
+            directory_dict.set_item("contributor_count", directory.contributor_count)?;
+            directory_dict.set_item("last_modified", directory.last_modified)?;
+            directory_dict.set_item("churn", directory.churn)?;
+            result.append(directory_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (subject_min_length = 10, subject_max_length = 72, issue_pattern = r"#\d+|\b[A-Z][A-Z0-9]+-\d+\b"))]
+    fn message_quality(
+        &self,
+        py: Python,
+        subject_min_length: usize,
+        subject_max_length: usize,
+        issue_pattern: &str,
+    ) -> PyResult<PyObject> {
+        if subject_min_length > subject_max_length {
+            return Err(PyValueError::new_err("subject_min_length must not exceed subject_max_length"));
+        }
+        let issue_pattern = Regex::new(issue_pattern).map_err(|err| {
+            PyValueError::new_err(format!("Invalid issue_pattern: {}", err))
+        })?;
+        let rules = MessageRules { subject_min_length, subject_max_length, issue_pattern };
+        let commits = self.collect_commits()?;
+        let (scores, distribution) = analysis::message_quality(&commits, &rules);
+        
+        let commit_list = PyList::empty(py);
+        for quality in scores {
+            let quality_dict = PyDict::new(py);
+            quality_dict.set_item("hash", &quality.hash)?;
+            quality_dict.set_item("has_body", quality.has_body)?;
+            quality_dict.set_item("subject_length_ok", quality.subject_length_ok)?;
+            quality_dict.set_item("references_issue", quality.references_issue)?;
+            quality_dict.set_item("conventional", quality.conventional)?;
+            quality_dict.set_item("score", quality.score)?;
+            commit_list.append(quality_dict)?;
+        }
+        
+        let result = PyDict::new(py);
+        result.set_item("commits", commit_list)?;
+        result.set_item("score_distribution", distribution)?;
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (test_patterns = None))]
+    fn test_discipline(&self, py: Python, test_patterns: Option<Vec<String>>) -> PyResult<PyObject> {
+        let collector = self.collector();
+        let commits = self.collect_commits()?;
+        let discipline = analysis::test_discipline(
+            &commits,
+            |filename| GitCollector::is_test_file(filename, test_patterns.as_deref()),
+            |filename| collector.is_code_file(filename),
+        );
+        
+        let commit_list = PyList::empty(py);
+        for (hash, category) in &discipline.categories {
+            let commit_dict = PyDict::new(py);
+            commit_dict.set_item("hash", hash)?;
+            commit_dict.set_item("category", test_category_name(*category))?;
+            commit_list.append(commit_dict)?;
+        }
+        
+        let counts = PyDict::new(py);
+        counts.set_item("code_only", discipline.code_only)?;
+        counts.set_item("tests_only", discipline.tests_only)?;
+        counts.set_item("code_and_tests", discipline.code_and_tests)?;
+        counts.set_item("neither", discipline.neither)?;
+        
+        let result = PyDict::new(py);
+        result.set_item("commits", commit_list)?;
+        result.set_item("counts", counts)?;
+        result.set_item("test_ratio", discipline.test_ratio)?;
+        Ok(result.into())
+    }
+    
+    fn review_stats(&self, py: Python) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        let stats = analysis::review_stats(&commits);
+        
+        let coverage = PyList::empty(py);
+        for author in stats.coverage {
+            let author_dict = PyDict::new(py);
+            author_dict.set_item("author", &author.author)?;
+            author_dict.set_item("commits", author.commits)?;
+            author_dict.set_item("reviewed_commits", author.reviewed_commits)?;
+            author_dict.set_item("coverage", author.coverage)?;
+            coverage.append(author_dict)?;
+        }
+        
+        let reviewers = PyList::empty(py);
+        for (reviewer, reviews) in stats.reviewers {
+            let reviewer_dict = PyDict::new(py);
+            reviewer_dict.set_item("reviewer", reviewer)?;
+            reviewer_dict.set_item("reviews", reviews)?;
+            reviewers.append(reviewer_dict)?;
+        }
+        
+        let result = PyDict::new(py);
+        result.set_item("coverage", coverage)?;
+        result.set_item("reviewers", reviewers)?;
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (window_days = 90, step_days = 30))]
+    fn active_contributors(&self, py: Python, window_days: u32, step_days: u32) -> PyResult<PyObject> {
+        if window_days == 0 || step_days == 0 {
+            return Err(PyValueError::new_err("window_days and step_days must be positive"));
+        }
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for point in analysis::active_contributors(&commits, window_days, step_days) {
+            let point_dict = PyDict::new(py);
+            point_dict.set_item("period_end", point.period_end)?;
+            point_dict.set_item("active_authors", point.active_authors)?;
+            result.append(point_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn resurrected_files(&self, py: Python) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for file in analysis::resurrected_files(&commits) {
+            let file_dict = PyDict::new(py);
+            file_dict.set_item("filename", &file.filename)?;
+            file_dict.set_item("deleted_in", &file.deleted_in)?;
+            file_dict.set_item("readded_in", &file.readded_in)?;
+            result.append(file_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn revert_pairs(&self, py: Python) -> PyResult<PyObject> {
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for pair in analysis::revert_pairs(&commits) {
+            result.append(revert_pair_to_py_dict(py, &pair)?)?;
+        }
+        Ok(result.into())
+    }
+    
+    /// Returns `None` when `commit_filter` rejects the commit.
+    fn get_commit(&self, py: Python, hash: &str) -> PyResult<PyObject> {
+        let commit = self.collector().get_commit(hash).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to get commit: {}", err))
+        })?;
+        match self.filter_commits(py, vec![commit])?.first() {
+            Some(commit) => commit_to_py_dict(py, commit),
+            None => Ok(py.None()),
+        }
+    }
+    
+    #[pyo3(signature = (reference = "HEAD"))]
+    fn reflog(&self, py: Python, reference: &str) -> PyResult<PyObject> {
+        let entries = self.collector().reflog(reference).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to read reflog: {}", err))
+        })?;
+        
+        let result = PyList::empty(py);
+        for entry in entries {
+            let entry_dict = PyDict::new(py);
+            entry_dict.set_item("ref", &entry.ref_name)?;
+            entry_dict.set_item("old_sha", &entry.old_sha)?;
+            entry_dict.set_item("new_sha", &entry.new_sha)?;
+            entry_dict.set_item("action", &entry.action)?;
+            entry_dict.set_item("message", &entry.message)?;
+            entry_dict.set_item("date", &entry.date)?;
+            result.append(entry_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn resolve_ref(&self, reference: &str) -> PyResult<String> {
+        self.collector().resolve_ref(reference).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to resolve ref: {}", err))
+        })
+    }
+    
+    fn branch_divergence(&self, py: Python, branch: &str, base: &str) -> PyResult<PyObject> {
+        let divergence = self.collector().branch_divergence(branch, base).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to compare branches: {}", err))
+        })?;
+        
+        let result = PyDict::new(py);
+        result.set_item("ahead", divergence.ahead)?;
+        result.set_item("behind", divergence.behind)?;
+        result.set_item("branch_files", divergence.branch_files)?;
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (order = "date"))]
+    fn tag_ranges(&self, py: Python, order: &str) -> PyResult<PyObject> {
+        let by_version = match order {
+            "date" => false,
+            "version" => true,
+            other => return Err(PyValueError::new_err(format!(
+                "Unknown tag order '{}', expected 'date' or 'version'", other
+            ))),
+        };
+        let ranges = self.collector().tag_ranges(by_version).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to compare tags: {}", err))
+        })?;
+        
+        let result = PyList::empty(py);
+        for range in ranges {
+            let range_dict = PyDict::new(py);
+            range_dict.set_item("from_tag", &range.from_tag)?;
+            range_dict.set_item("to_tag", &range.to_tag)?;
+            range_dict.set_item("commit_count", range.commit_count)?;
+            range_dict.set_item("files_changed", range.files_changed)?;
+            range_dict.set_item("insertions", range.insertions)?;
+            range_dict.set_item("deletions", range.deletions)?;
+            result.append(range_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn merge_base(&self, ref_a: &str, ref_b: &str) -> PyResult<String> {
+        self.collector().merge_base(ref_a, ref_b).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to find merge base: {}", err))
+        })
+    }
+    
+    #[pyo3(signature = (path, format = "json"))]
+    fn export_history(&self, path: &str, format: &str) -> PyResult<usize> {
+        let format = parse_cache_format(format)?;
+        let exported = match &self.commit_filter {
+            Some(_) => GitCollector::write_export(path, &self.collect_commits()?, format),
+            None => self.collector().export_history(path, format),
+        };
+        exported.map_err(|err| PyRuntimeError::new_err(format!("Failed to export history: {}", err)))
+    }
+    
+    #[staticmethod]
+    #[pyo3(signature = (path, format = "json"))]
+    fn load_export(py: Python, path: &str, format: &str) -> PyResult<PyObject> {
+        let commits = GitCollector::read_export(path, parse_cache_format(format)?).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to load export: {}", err))
+        })?;
+        
+        let result = PyList::empty(py);
+        for commit in &commits {
+            result.append(commit_to_py_dict(py, commit)?)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn get_commits(&self, py: Python, hashes: Vec<String>) -> PyResult<PyObject> {
+        let commits = self.collector().get_commits(&hashes).map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to get commits: {}", err))
+        })?;
+        let commits = self.filter_commits(py, commits)?;
+        
+        let result = PyList::empty(py);
+        for commit in &commits {
+            result.append(commit_to_py_dict(py, commit)?)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn list_tracked_files(&self) -> PyResult<Vec<String>> {
+        self.collector().list_tracked_files().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to list tracked files: {}", err))
+        })
+    }
+    
+    fn raw_log(&self, py: Python) -> PyResult<PyObject> {
+        let output = self.collector().raw_log().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to read raw log: {}", err))
+        })?;
+        
+        Ok(PyBytes::new(py, &output).into())
+    }
+    
+    fn line_counts(&self, py: Python) -> PyResult<PyObject> {
+        let counts = self.collector().line_counts().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to count lines: {}", err))
+        })?;
+        
+        let result = PyDict::new(py);
+        for count in counts {
+            let count_dict = PyDict::new(py);
+            count_dict.set_item("lines", count.lines)?;
+            count_dict.set_item("binary", count.binary)?;
+            result.set_item(&count.filename, count_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    #[pyo3(signature = (days = 180))]
+    fn stale_files(&self, py: Python, days: u32) -> PyResult<PyObject> {
+        let tracked_files = self.collector().list_tracked_files().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to list tracked files: {}", err))
+        })?;
+        let commits = self.collect_commits()?;
+        
+        let result = PyList::empty(py);
+        for file in analysis::stale_files(&tracked_files, &commits, days, Utc::now().timestamp()) {
+            let file_dict = PyDict::new(py);
+            file_dict.set_item("filename", &file.filename)?;
+            file_dict.set_item("last_modified", file.last_modified)?;
+            result.append(file_dict)?;
+        }
+        Ok(result.into())
+    }
+    
+    fn primary_language(&self, py: Python) -> PyResult<PyObject> {
+        let counts = self.collector().line_counts().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to count lines: {}", err))
+        })?;
+        
+        let Some(primary) = analysis::primary_language(&counts) else {
+            return Ok(py.None());
+        };
+        let result = PyDict::new(py);
+        result.set_item("language", primary.language)?;
+        result.set_item("lines", primary.lines)?;
+        result.set_item("total_lines", primary.total_lines)?;
+        result.set_item("percentage", primary.percentage)?;
+        Ok(result.into())
+    }
+    
+    fn current_branch(&self) -> PyResult<Option<String>> {
+        self.collector().current_branch().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to get current branch: {}", err))
+        })
+    }
+    
+    fn head_sha(&self) -> PyResult<String> {
+        self.collector().head_sha().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to get HEAD sha: {}", err))
+        })
+    }
+    
+    fn plan(&self) -> PyResult<Vec<String>> {
+        self.collector().plan().map_err(|err| {
+            PyValueError::new_err(format!("Invalid collection options: {}", err))
+        })
+    }
+    
+    fn git_version(&self) -> PyResult<Option<(u32, u32, u32)>> {
+        self.collector().git_version().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to get git version: {}", err))
+        })
+    }
+    
+    fn last_command(&self) -> Option<String> {
+        self.collector().last_command()
+    }
+    
+    fn is_shallow(&self) -> PyResult<bool> {
+        self.collector().is_shallow().map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to check repository depth: {}", err))
+        })
+    }
 
     fn clear_cache(&self) -> PyResult<()> {
         let collector = GitCollector::new(&self.repo_path, None, None, Vec::new());
@@ -102,25 +1199,207 @@ impl RustGitCollector {
     }
 }
 
+fn changes_to_py_dict(py: Python, changes: HashMap<String, HashMap<String, u32>>) -> PyResult<PyObject> {
+    let result = PyDict::new(py);
+    
+    for (file, change_data) in changes {
+        let data_dict = PyDict::new(py);
+        
+        for (key, value) in change_data {
+            data_dict.set_item(key, value)?;
+        }
+        
+        result.set_item(file, data_dict)?;
+    }
+    
+    Ok(result.into())
+}
+
 fn commit_to_py_dict(py: Python, commit: &Commit) -> PyResult<PyObject> {
     let commit_dict = PyDict::new(py);
     
     commit_dict.set_item("hash", &commit.hash)?;
+    commit_dict.set_item("short_hash", &commit.short_hash)?;
     commit_dict.set_item("author", &commit.author)?;
+    commit_dict.set_item("author_email", &commit.author_email)?;
     commit_dict.set_item("date", &commit.date)?;
+    commit_dict.set_item("timestamp", commit.timestamp)?;
+    commit_dict.set_item("date_utc", &commit.date_utc)?;
+    commit_dict.set_item("utc_offset_minutes", commit.utc_offset_minutes)?;
+    commit_dict.set_item("parents", commit.parents.clone())?;
+    commit_dict.set_item("is_root", commit.is_root)?;
+    commit_dict.set_item("tree_hash", &commit.tree_hash)?;
+    commit_dict.set_item("is_code_change", commit.is_code_change)?;
+    commit_dict.set_item("is_whitespace_only", commit.is_whitespace_only)?;
+    commit_dict.set_item("signature_status", &commit.signature_status)?;
+    commit_dict.set_item("signer", &commit.signer)?;
+    commit_dict.set_item("signing_key", &commit.signing_key)?;
+    commit_dict.set_item("encoding", &commit.encoding)?;
     commit_dict.set_item("message", &commit.message)?;
+    commit_dict.set_item("body", &commit.body)?;
+    commit_dict.set_item("reverted_hash", &commit.reverted_hash)?;
+    commit_dict.set_item("trailers", &commit.trailers)?;
+    commit_dict.set_item("index", commit.index)?;
     
     let files = PyList::empty(py);
     for file in &commit.files {
         let file_dict = PyDict::new(py);
         file_dict.set_item("filename", &file.filename)?;
+        file_dict.set_item("old_filename", &file.old_filename)?;
         file_dict.set_item("status", &file.status)?;
         file_dict.set_item("additions", file.additions)?;
         file_dict.set_item("deletions", file.deletions)?;
+        file_dict.set_item("binary", file.binary)?;
+        file_dict.set_item("from_merge", file.from_merge)?;
+        file_dict.set_item("capped", file.capped)?;
+        file_dict.set_item("raw_additions", file.raw_additions)?;
+        file_dict.set_item("raw_deletions", file.raw_deletions)?;
+        file_dict.set_item("is_symlink", file.is_symlink)?;
         files.append(file_dict)?;
     }
     
     commit_dict.set_item("files", files)?;
+    commit_dict.set_item("files_added", commit.files_added)?;
+    commit_dict.set_item("files_modified", commit.files_modified)?;
+    commit_dict.set_item("files_deleted", commit.files_deleted)?;
+    commit_dict.set_item("files_renamed", commit.files_renamed)?;
     
     Ok(commit_dict.into())
 }
+
+fn file_churn_to_py_dict(py: Python, churn: &FileChurn) -> PyResult<PyObject> {
+    let churn_dict = PyDict::new(py);
+    
+    churn_dict.set_item("filename", &churn.filename)?;
+    churn_dict.set_item("additions", churn.additions)?;
+    churn_dict.set_item("deletions", churn.deletions)?;
+    churn_dict.set_item("churn", churn.churn)?;
+    churn_dict.set_item("net_lines", churn.net_lines)?;
+    churn_dict.set_item("weighted_churn", churn.weighted_churn)?;
+    
+    Ok(churn_dict.into())
+}
+
+fn file_ownership_to_py_dict(py: Python, ownership: &FileOwnership) -> PyResult<PyObject> {
+    let ownership_dict = PyDict::new(py);
+    
+    ownership_dict.set_item("filename", &ownership.filename)?;
+    ownership_dict.set_item("dominant_author", &ownership.dominant_author)?;
+    ownership_dict.set_item("ownership_ratio", ownership.ownership_ratio)?;
+    ownership_dict.set_item("contributor_count", ownership.contributor_count)?;
+    ownership_dict.set_item("total_changes", ownership.total_changes)?;
+    ownership_dict.set_item("author_changes", ownership.author_changes.clone())?;
+    
+    Ok(ownership_dict.into())
+}
+
+fn author_edge_to_py_dict(py: Python, edge: &AuthorEdge) -> PyResult<PyObject> {
+    let edge_dict = PyDict::new(py);
+    
+    edge_dict.set_item("author_a", &edge.author_a)?;
+    edge_dict.set_item("author_b", &edge.author_b)?;
+    edge_dict.set_item("weight", edge.weight)?;
+    
+    Ok(edge_dict.into())
+}
+
+fn revert_pair_to_py_dict(py: Python, pair: &RevertPair) -> PyResult<PyObject> {
+    let pair_dict = PyDict::new(py);
+    
+    pair_dict.set_item("original_hash", &pair.original_hash)?;
+    pair_dict.set_item("revert_hash", &pair.revert_hash)?;
+    pair_dict.set_item("resolved", pair.resolved)?;
+    pair_dict.set_item("time_to_revert_seconds", pair.time_to_revert_seconds)?;
+    
+    Ok(pair_dict.into())
+}
+
+fn next_commit_dict(py: Python, stream: &mut CommitStream, commit_filter: Option<&PyObject>) -> PyResult<Option<PyObject>> {
+    for result in stream {
+        let commit = result.map_err(|err| {
+            PyRuntimeError::new_err(format!("Failed to stream history: {}", err))
+        })?;
+        let commit_dict = commit_to_py_dict(py, &commit)?;
+        let keep = match commit_filter {
+            Some(predicate) => predicate.call1(py, (commit_dict.clone_ref(py),))?.is_true(py)?,
+            None => true,
+        };
+        if keep {
+            return Ok(Some(commit_dict));
+        }
+    }
+    Ok(None)
+}
+
+fn keeps_commit(py: Python, predicate: &PyObject, commit: &Commit) -> PyResult<bool> {
+    predicate.call1(py, (commit_to_py_dict(py, commit)?,))?.is_true(py)
+}
+
+fn parse_cache_format(format: &str) -> PyResult<CacheFormat> {
+    match format {
+        "json" => Ok(CacheFormat::Json),
+        "bincode" => Ok(CacheFormat::Bincode),
+        other => Err(PyValueError::new_err(format!(
+            "Unknown format '{}', expected 'json' or 'bincode'", other
+        ))),
+    }
+}
+
+fn test_category_name(category: TestCategory) -> &'static str {
+    match category {
+        TestCategory::CodeOnly => "code_only",
+        TestCategory::TestsOnly => "tests_only",
+        TestCategory::CodeAndTests => "code_and_tests",
+        TestCategory::Neither => "neither",
+    }
+}
+
+fn parse_traversal_order(order: &str) -> PyResult<TraversalOrder> {
+    match order {
+        "date" => Ok(TraversalOrder::Date),
+        "author-date" => Ok(TraversalOrder::AuthorDate),
+        "topo" => Ok(TraversalOrder::Topo),
+        other => Err(PyValueError::new_err(format!(
+            "Unknown traversal order '{}', expected 'date', 'author-date' or 'topo'", other
+        ))),
+    }
+}
+
+// "author" keeps each commit's own offset; otherwise "utc" or a fixed offset like "+02:00".
+fn parse_timezone(timezone: &str) -> PyResult<Option<i32>> {
+    match timezone {
+        "author" => return Ok(None),
+        "utc" | "UTC" | "Z" => return Ok(Some(0)),
+        _ => {},
+    }
+    
+    let invalid = || PyValueError::new_err(format!(
+        "Unknown timezone '{}', expected 'author', 'utc' or an offset like '+02:00'", timezone
+    ));
+    let (sign, digits) = match timezone.split_at_checked(1) {
+        Some(("+", digits)) => (1, digits),
+        Some(("-", digits)) => (-1, digits),
+        _ => return Err(invalid()),
+    };
+    let digits = digits.replace(':', "");
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+    let minutes: i32 = digits[2..].parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes >= 60 {
+        return Err(invalid());
+    }
+    Ok(Some(sign * (hours * 60 + minutes)))
+}
+
+fn parse_diff_mode(mode: &str) -> PyResult<DiffMode> {
+    match mode {
+        "unstaged" => Ok(DiffMode::Unstaged),
+        "staged" => Ok(DiffMode::Staged),
+        "both" => Ok(DiffMode::Both),
+        other => Err(PyValueError::new_err(format!(
+            "Unknown diff mode '{}', expected 'unstaged', 'staged' or 'both'", other
+        ))),
+    }
+}
@@ -1,18 +1,325 @@
+use std::collections::HashMap;
+
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffMode {
+    Unstaged,
+    Staged,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheFormat {
+    Json,
+    Bincode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraversalOrder {
+    Date,
+    AuthorDate,
+    Topo,
+}
+
+#[derive(Debug, Clone)]
+pub struct MessageRules {
+    pub subject_min_length: usize,
+    pub subject_max_length: usize,
+    pub issue_pattern: Regex,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct FileChange {
     pub filename: String,
+    pub old_filename: Option<String>,
     pub status: String,
     pub additions: u32,
     pub deletions: u32,
+    pub binary: bool,
+    pub from_merge: bool,
+    /// Set when numstat exceeded the per-file line cap and the counts were clamped.
+    pub capped: bool,
+    pub raw_additions: u32,
+    pub raw_deletions: u32,
+    pub is_symlink: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Commit {
     pub hash: String,
+    pub short_hash: String,
     pub author: String,
+    pub author_email: String,
     pub date: String,
+    pub timestamp: i64,
+    pub date_utc: String,
+    pub utc_offset_minutes: i32,
+    pub parents: Vec<String>,
+    pub is_root: bool,
+    pub tree_hash: String,
+    pub is_code_change: bool,
+    pub is_whitespace_only: bool,
+    /// Whether numstat reported changed lines (or a binary change) in the kept files. Only set
+    /// while parsing, for the whitespace-only pass, so it isn't cached.
+    #[serde(skip)]
+    pub has_line_changes: bool,
     pub message: String,
+    pub body: String,
+    pub reverted_hash: Option<String>,
+    /// Trailers from the body's last paragraph, keys as written, values in order.
+    pub trailers: HashMap<String, Vec<String>>,
+    pub signature_status: Option<String>,
+    pub signer: Option<String>,
+    pub signing_key: Option<String>,
+    pub encoding: Option<String>,
+    pub files_added: usize,
+    pub files_modified: usize,
+    pub files_deleted: usize,
+    pub files_renamed: usize,
     pub files: Vec<FileChange>,
+    pub index: usize,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileChurn {
+    pub filename: String,
+    pub additions: u32,
+    pub deletions: u32,
+    pub churn: u32,
+    pub net_lines: i64,
+    pub weighted_churn: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileOwnership {
+    pub filename: String,
+    pub dominant_author: String,
+    pub ownership_ratio: f64,
+    pub contributor_count: usize,
+    pub total_changes: u32,
+    pub author_changes: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectorySort {
+    Churn,
+    Staleness,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DirectoryReport {
+    pub directory: String,
+    pub dominant_author: String,
+    pub ownership_ratio: f64,
+    pub contributor_count: usize,
+    pub last_modified: i64,
+    pub churn: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DirectoryTruckFactor {
+    pub directory: String,
+    pub truck_factor: usize,
+    pub key_authors: Vec<String>,
+    pub total_changes: u32,
+    pub high_risk: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OwnedFilesCommit {
+    pub commit: Commit,
+    pub owned_files: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RevertPair {
+    pub original_hash: String,
+    pub revert_hash: String,
+    pub resolved: bool,
+    pub time_to_revert_seconds: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileLineCount {
+    pub filename: String,
+    pub lines: usize,
+    pub binary: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageQuality {
+    pub hash: String,
+    pub has_body: bool,
+    pub subject_length_ok: bool,
+    pub references_issue: bool,
+    pub conventional: bool,
+    /// Number of the checks above that passed, 0 to 4.
+    pub score: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StaleFile {
+    pub filename: String,
+    /// `None` when the file has no change in the collected history at all.
+    pub last_modified: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestCategory {
+    CodeOnly,
+    TestsOnly,
+    CodeAndTests,
+    Neither,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TestDiscipline {
+    pub categories: Vec<(String, TestCategory)>,
+    pub code_only: usize,
+    pub tests_only: usize,
+    pub code_and_tests: usize,
+    pub neither: usize,
+    /// Share of code-changing commits that also touch tests; `None` without code changes.
+    pub test_ratio: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReviewCoverage {
+    pub author: String,
+    pub commits: usize,
+    pub reviewed_commits: usize,
+    pub coverage: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReviewStats {
+    pub coverage: Vec<ReviewCoverage>,
+    /// Reviewer identity and the number of commits they reviewed, most active first.
+    pub reviewers: Vec<(String, usize)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PrimaryLanguage {
+    pub language: String,
+    pub lines: usize,
+    pub total_lines: usize,
+    pub percentage: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthorEdge {
+    pub author_a: String,
+    pub author_b: String,
+    pub weight: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CommitSizeStats {
+    pub commit_count: usize,
+    pub mean: f64,
+    pub max: u32,
+    pub p50: u32,
+    pub p90: u32,
+    pub p99: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RelativeChurn {
+    pub filename: String,
+    pub churn: u32,
+    pub lines: usize,
+    pub relative_churn: f64,
+    pub unstable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CommitCadence {
+    pub median_gap_hours: Option<f64>,
+    pub commit_count: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuthorMonthMatrix {
+    pub authors: Vec<String>,
+    pub months: Vec<String>,
+    pub counts: Vec<Vec<u32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChurnVelocity {
+    pub bucket_start: i64,
+    pub churn: u32,
+    pub velocity: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GrowthRatio {
+    pub bucket_start: i64,
+    pub additions: u32,
+    pub deletions: u32,
+    /// Additions per deleted line; `None` when nothing was deleted in the bucket.
+    pub ratio: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExtensionChurnBucket {
+    pub bucket_start: i64,
+    pub additions: u32,
+    pub deletions: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActiveContributors {
+    pub period_end: i64,
+    pub active_authors: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheStatus {
+    pub commit_count: usize,
+    pub rebuilt: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResurrectedFile {
+    pub filename: String,
+    pub deleted_in: String,
+    pub readded_in: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReflogEntry {
+    pub ref_name: String,
+    pub old_sha: Option<String>,
+    pub new_sha: String,
+    pub action: String,
+    pub message: String,
+    pub date: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BranchDivergence {
+    pub ahead: usize,
+    pub behind: usize,
+    pub branch_files: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DiffSummary {
+    pub files_changed: u32,
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TagRange {
+    pub from_tag: String,
+    pub to_tag: String,
+    pub commit_count: usize,
+    pub files_changed: u32,
+    pub insertions: u32,
+    pub deletions: u32,
 }
@@ -81,7 +81,7 @@ def show_metrics(
     metrics: Optional[List[str]] = typer.Option(None, "--metrics", "-m", help="Specific metrics to analyze (omit for all)"),
     max_commits: Optional[int] = typer.Option(None, "--max-commits", help="Maximum number of commits to analyze"),
     since_days: Optional[int] = typer.Option(None, "--since-days", help="Analyze commits from the last N days"),
-    file_patterns: Optional[List[str]] = typer.Option(None, "--files", "-f", help="File patterns to filter (e.g. '*.py', 'src/*')"),
+    file_patterns: Optional[List[str]] = typer.Option(None, "--files", "-f", help="File patterns to filter, matched from the repository root (e.g. '**/*.py', 'src/*')"),
     use_python: bool = typer.Option(False, "--use-python", help="Force using Python implementation instead of Rust"),
     clear: bool = typer.Option(False, "--clear-cache", help="Clear the commit data cache"),
 ):
@@ -106,7 +106,7 @@ def analyze_impact(
     metrics: Optional[List[str]] = typer.Option(None, "--metrics", "-m", help="Specific metrics to analyze (omit for all)"),
     max_commits: Optional[int] = typer.Option(None, "--max-commits", help="Maximum number of commits to analyze"),
     since_days: Optional[int] = typer.Option(None, "--since-days", help="Analyze commits from the last N days"),
-    file_patterns: Optional[List[str]] = typer.Option(None, "--files", "-f", help="File patterns to filter (e.g. '*.py', 'src/*')"),
+    file_patterns: Optional[List[str]] = typer.Option(None, "--files", "-f", help="File patterns to filter, matched from the repository root (e.g. '**/*.py', 'src/*')"),
     use_python: bool = typer.Option(False, "--use-python", help="Force using Python implementation instead of Rust"),
 ):
     plugin_manager = PluginManager()
@@ -107,11 +107,15 @@ class GitPythonCollector:
             return True
 
         for pattern in self.file_patterns:
-            if pattern.startswith("*.") and filename.endswith(pattern[1:]):
-                return True
+            if pattern.endswith("/"):
+                if filename.startswith(pattern):
+                    return True
             elif "*" in pattern:
-                regex_pattern = pattern.replace(".", "\\.").replace("*", ".*")
-                if re.match(regex_pattern, filename):
+                regex_pattern = (re.escape(pattern)
+                                 .replace("\\*\\*/", "(?:.*/)?")
+                                 .replace("\\*\\*", ".*")
+                                 .replace("\\*", "[^/]*"))
+                if re.fullmatch(regex_pattern, filename):
                     return True
             elif filename == pattern:
                 return True